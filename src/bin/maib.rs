@@ -0,0 +1,496 @@
+//! Command-line tool for MAIB MIA API.
+//!
+//! Available with `cli` feature.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use chrono::{DateTime, Duration, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use maib_client::{
+    blocking::Client,
+    config::{Config, ConfigError, ConfigLoader},
+    error::Error,
+    models::{
+        request::{CancelQR, CreateQR, ListPayments, ListQRs, RefundPayment},
+        AccessToken, NotificationPayload, PaymentId, PaymentStatus, QRId, QRStatus, QRType,
+    },
+    token_store::FileTokenStore,
+};
+use rust_decimal::Decimal;
+
+#[derive(Debug, Parser)]
+#[command(name = "maib", version, about = "Command-line tool for MAIB MIA API")]
+struct Cli {
+    /// TOML or JSON config file, with named profiles.
+    ///
+    /// Every value is also read from `MAIB_<KEY>` and `MAIB_<PROFILE>_<KEY>`
+    /// env variables, e.g. `MAIB_CLIENT_ID`. Env variables override the
+    /// file, flags override both.
+    #[arg(long, env = "MAIB_CONFIG", global = true)]
+    config: Option<PathBuf>,
+
+    /// Profile of config file and env variables, `MAIB_PROFILE` if not set.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    #[arg(long, global = true)]
+    base_url: Option<String>,
+
+    #[arg(long, global = true)]
+    client_id: Option<String>,
+
+    #[arg(long, global = true)]
+    client_secret: Option<String>,
+
+    /// Use this token instead of fetching a new one.
+    #[arg(long, global = true)]
+    access_token: Option<String>,
+
+    /// File to cache access tokens in between runs.
+    #[arg(long, env = "MAIB_TOKEN_CACHE", global = true)]
+    token_cache: Option<PathBuf>,
+
+    #[arg(long, global = true)]
+    signature_key: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Output::Json, global = true)]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Output {
+    Json,
+    Table,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Fetch a new access token.
+    Token,
+
+    /// Manage QR codes.
+    #[command(subcommand)]
+    Qr(QrCommand),
+
+    /// Manage payments.
+    #[command(subcommand)]
+    Payment(PaymentCommand),
+
+    /// Verify signature of a notification stored in a file.
+    VerifyWebhook {
+        /// File with notification body, as received on callback url.
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum QrCommand {
+    /// Create dynamic QR with fixed amount.
+    Create(CreateQrArgs),
+    Get {
+        qr_id: String,
+    },
+    Cancel {
+        qr_id: String,
+        #[arg(long)]
+        reason: String,
+    },
+    List(ListQrArgs),
+}
+
+#[derive(Debug, Args)]
+struct CreateQrArgs {
+    #[arg(long)]
+    amount: Decimal,
+    #[arg(long)]
+    description: String,
+    /// ISO 8601 date time, defaults to an hour from now.
+    #[arg(long)]
+    expires_at: Option<String>,
+    #[arg(long)]
+    order_id: Option<String>,
+    #[arg(long, default_value = "")]
+    callback_url: String,
+    #[arg(long, default_value = "")]
+    redirect_url: String,
+    #[arg(long)]
+    terminal_id: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct ListQrArgs {
+    #[arg(long)]
+    count: Option<u32>,
+    #[arg(long)]
+    offset: Option<u32>,
+    #[arg(long)]
+    order_id: Option<String>,
+    #[arg(long)]
+    r#type: Option<String>,
+    #[arg(long)]
+    status: Option<String>,
+    #[arg(long)]
+    terminal_id: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum PaymentCommand {
+    Get {
+        pay_id: String,
+    },
+    /// Refund payment, whole remaining amount unless `--amount` is set.
+    Refund {
+        pay_id: String,
+        #[arg(long)]
+        reason: String,
+        #[arg(long)]
+        amount: Option<Decimal>,
+        /// Url notified about refund status.
+        #[arg(long)]
+        callback_url: Option<String>,
+    },
+    List(ListPaymentArgs),
+}
+
+#[derive(Debug, Args)]
+struct ListPaymentArgs {
+    #[arg(long)]
+    count: Option<u32>,
+    #[arg(long)]
+    offset: Option<u32>,
+    #[arg(long)]
+    qr_id: Option<String>,
+    #[arg(long)]
+    order_id: Option<String>,
+    #[arg(long)]
+    status: Option<String>,
+    #[arg(long)]
+    terminal_id: Option<String>,
+    /// RFC 3339 date time, only payments executed at or after it.
+    #[arg(long)]
+    executed_from: Option<DateTime<Utc>>,
+    /// RFC 3339 date time, only payments executed before it.
+    #[arg(long)]
+    executed_to: Option<DateTime<Utc>>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = load_config(&cli).and_then(|config| run(&cli, &config));
+    return match result {
+        Ok(value) => {
+            print(&value, cli.output);
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    };
+}
+
+/// Load config from file, env variables and flags.
+fn load_config(cli: &Cli) -> Result<Config, String> {
+    let mut loader = ConfigLoader::new();
+    if let Some(ref path) = cli.config {
+        loader = loader.file(path);
+    }
+    if let Some(ref profile) = cli.profile {
+        loader = loader.profile(profile);
+    }
+
+    for (key, value) in [
+        ("base_url", &cli.base_url),
+        ("client_id", &cli.client_id),
+        ("client_secret", &cli.client_secret),
+        ("access_token", &cli.access_token),
+        ("signature_key", &cli.signature_key),
+    ] {
+        if let Some(value) = value {
+            loader = loader.set(key, value);
+        }
+    }
+
+    // Notifications are verified offline, base url is not used.
+    if let Command::VerifyWebhook { .. } = cli.command {
+        loader = loader.default_value("base_url", "https://api.maibmerchants.md");
+    }
+
+    return loader.load().map_err(config_error);
+}
+
+fn run(cli: &Cli, config: &Config) -> Result<serde_json::Value, String> {
+    if let Command::VerifyWebhook { ref file } = cli.command {
+        return verify_webhook(config, file);
+    }
+
+    let client = config.blocking_client().map_err(api_error)?;
+
+    if let Command::Token = cli.command {
+        return to_json(fetch_token(config, &client)?);
+    }
+
+    let token = match config.access_token {
+        Some(ref token) => token.clone(),
+        None => match cli.token_cache {
+            Some(ref path) => cached_token(config, &client, path)?,
+            None => fetch_token(config, &client)?.take_access_token(),
+        },
+    };
+
+    return match cli.command {
+        Command::Qr(ref command) => run_qr(&client, config, &token, command),
+        Command::Payment(ref command) => run_payment(&client, &token, command),
+        Command::Token | Command::VerifyWebhook { .. } => unreachable!(),
+    };
+}
+
+fn run_qr(
+    client: &Client,
+    config: &Config,
+    token: &AccessToken,
+    command: &QrCommand,
+) -> Result<serde_json::Value, String> {
+    return match command {
+        QrCommand::Create(args) => {
+            let expires_at = match args.expires_at {
+                Some(ref expires_at) => expires_at.clone(),
+                None => (Utc::now() + Duration::hours(1)).to_rfc3339(),
+            };
+
+            let mut payload = CreateQR::new_dynamic_with_fixed_amount(
+                args.amount,
+                &expires_at,
+                args.description.clone(),
+                args.callback_url.clone(),
+                args.redirect_url.clone(),
+            );
+            payload.order_id = args.order_id.as_deref();
+            payload.terminal_id = args.terminal_id.clone();
+            config.apply_defaults(&mut payload);
+
+            to_json(client.create_qr(&payload, token).map_err(api_error)?)
+        }
+        QrCommand::Get { qr_id } => {
+            let qr_id = QRId::new(qr_id.clone());
+            to_json(client.get_qr(&qr_id, token).map_err(api_error)?)
+        }
+        QrCommand::Cancel { qr_id, reason } => {
+            let qr_id = QRId::new(qr_id.clone());
+            let payload = CancelQR {
+                reason: reason.clone(),
+            };
+            to_json(
+                client
+                    .cancel_qr(&qr_id, &payload, token)
+                    .map_err(api_error)?,
+            )
+        }
+        QrCommand::List(args) => {
+            let query = ListQRs {
+                count: args.count,
+                offset: args.offset,
+                qr_id: None,
+                order_id: args.order_id.clone(),
+                r#type: args.r#type.as_deref().map(QRType::from),
+                status: args.status.as_deref().map(QRStatus::from),
+                terminal_id: args.terminal_id.clone(),
+            };
+            to_json(client.list_qrs(&query, token).map_err(api_error)?)
+        }
+    };
+}
+
+fn run_payment(
+    client: &Client,
+    token: &AccessToken,
+    command: &PaymentCommand,
+) -> Result<serde_json::Value, String> {
+    return match command {
+        PaymentCommand::Get { pay_id } => {
+            let pay_id = PaymentId::new(pay_id.clone());
+            to_json(client.get_payment(&pay_id, token).map_err(api_error)?)
+        }
+        PaymentCommand::Refund {
+            pay_id,
+            reason,
+            amount,
+            callback_url,
+        } => {
+            let pay_id = PaymentId::new(pay_id.clone());
+            let payload = RefundPayment {
+                reason: reason.clone(),
+                amount: *amount,
+                callback_url: callback_url.clone(),
+            };
+
+            let payment = client.get_payment(&pay_id, token).map_err(api_error)?;
+            to_json(
+                client
+                    .refund_payment_checked(&payment, &payload, token)
+                    .map_err(api_error)?,
+            )
+        }
+        PaymentCommand::List(args) => {
+            let query = ListPayments {
+                count: args.count,
+                offset: args.offset,
+                pay_id: None,
+                qr_id: args.qr_id.clone().map(QRId::new),
+                order_id: args.order_id.clone(),
+                status: args.status.as_deref().map(PaymentStatus::from),
+                terminal_id: args.terminal_id.clone(),
+                executed_at_from: args.executed_from,
+                executed_at_to: args.executed_to,
+            };
+            to_json(client.list_payments(&query, token).map_err(api_error)?)
+        }
+    };
+}
+
+fn fetch_token(
+    config: &Config,
+    client: &Client,
+) -> Result<maib_client::models::response::AuthToken, String> {
+    let (Some(id), Some(secret)) = (&config.client_id, &config.client_secret) else {
+        return Err(config_error(ConfigError::Missing("client_id")));
+    };
+
+    return client.get_access_token(id, secret).map_err(api_error);
+}
+
+fn cached_token(config: &Config, client: &Client, path: &PathBuf) -> Result<AccessToken, String> {
+    let (Some(id), Some(secret)) = (&config.client_id, &config.client_secret) else {
+        return Err(config_error(ConfigError::Missing("client_id")));
+    };
+
+    return client
+        .cached_access_token(&FileTokenStore::new(path), id, secret)
+        .map_err(api_error);
+}
+
+fn verify_webhook(config: &Config, file: &PathBuf) -> Result<serde_json::Value, String> {
+    let Some(ref key) = config.signature_key else {
+        return Err(config_error(ConfigError::Missing("signature_key")));
+    };
+    let contents =
+        std::fs::read(file).map_err(|err| format!("can not read {}: {err}", file.display()))?;
+    let payload: NotificationPayload = serde_json::from_slice(&contents)
+        .map_err(|err| format!("invalid notification {}: {err}", file.display()))?;
+
+    return match payload.validate_signature(key.clone()) {
+        Some(notification) => to_json(notification.0),
+        None => Err("signature is not valid".to_owned()),
+    };
+}
+
+fn config_error(err: ConfigError) -> String {
+    return match err {
+        ConfigError::Missing(key) => format!(
+            "{key} is not set, use --config, --{} or MAIB_{}",
+            key.replace('_', "-"),
+            key.to_uppercase()
+        ),
+        err => err.to_string(),
+    };
+}
+
+fn to_json<T: serde::Serialize>(value: T) -> Result<serde_json::Value, String> {
+    return serde_json::to_value(value).map_err(|err| format!("can not serialize result: {err}"));
+}
+
+fn api_error(err: Error) -> String {
+    return match err {
+        Error::Unauthorized => "unauthorized, access token is not valid".to_owned(),
+        Error::Http(message) | Error::Json(message) | Error::TokenStore(message) => message,
+        Error::RefundExceedsRemaining {
+            requested,
+            remaining,
+        } => format!("can not refund {requested}, only {remaining} remains"),
+        Error::RefundedAmountMismatch { expected, refunded } => {
+            format!("expected {expected} to be refunded before, but {refunded} is")
+        }
+        Error::Api(errors) => errors
+            .iter()
+            .map(|err| format!("{}: {}", err.code(), err.message()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        err => format!("{err:?}"),
+    };
+}
+
+fn print(value: &serde_json::Value, output: Output) {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(value).unwrap()),
+        Output::Table => print!("{}", table(value)),
+    }
+}
+
+/// Lists are printed with a column per field, objects with a row per field.
+fn table(value: &serde_json::Value) -> String {
+    let items = value.get("items").and_then(serde_json::Value::as_array);
+
+    let rows: Vec<Vec<String>> = match (items, value.as_object()) {
+        (Some(items), _) => {
+            let mut columns: Vec<&String> = Vec::new();
+            for item in items.iter().filter_map(serde_json::Value::as_object) {
+                for key in item.keys() {
+                    if !columns.contains(&key) {
+                        columns.push(key);
+                    }
+                }
+            }
+
+            let header = columns.iter().map(|key| key.to_string()).collect();
+            let rows = items.iter().map(|item| {
+                columns
+                    .iter()
+                    .map(|key| cell(&item[key.as_str()]))
+                    .collect()
+            });
+
+            std::iter::once(header).chain(rows).collect()
+        }
+        (None, Some(object)) => object
+            .iter()
+            .map(|(key, value)| vec![key.clone(), cell(value)])
+            .collect(),
+        (None, None) => vec![vec![cell(value)]],
+    };
+
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut out = String::new();
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        out.push_str(line.join("  ").trim_end());
+        out.push('\n');
+    }
+
+    return out;
+}
+
+fn cell(value: &serde_json::Value) -> String {
+    return match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+}
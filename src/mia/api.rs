@@ -0,0 +1,128 @@
+use core::future::Future;
+
+use crate::{
+    client::Client,
+    error::Result,
+    models::{
+        request::{self, CancelQR, ListPayments, ListQRs, RefundPayment},
+        response::{self, AuthToken},
+        AccessToken, ClientId, ClientSecret, PaymentId, QRId,
+    },
+};
+
+/// Operations of MIA API.
+///
+/// Implemented by [Client], depend on this trait to be able to
+/// substitute the client in tests, see `testing::MockMiaApi`.
+pub trait MiaApi {
+    /// Attempt to fetch a new [AccessToken]
+    fn get_access_token(
+        &self,
+        id: &ClientId,
+        secret: &ClientSecret,
+    ) -> impl Future<Output = Result<AuthToken>> + Send;
+
+    fn create_qr(
+        &self,
+        payload: &request::CreateQR<'_>,
+        token: &AccessToken,
+    ) -> impl Future<Output = Result<response::CreateQRResponse>> + Send;
+
+    fn get_qr(
+        &self,
+        qr_id: &QRId,
+        token: &AccessToken,
+    ) -> impl Future<Output = Result<response::GetQRDetails>> + Send;
+
+    fn cancel_qr(
+        &self,
+        qr_id: &QRId,
+        payload: &CancelQR,
+        token: &AccessToken,
+    ) -> impl Future<Output = Result<response::CancelQR>> + Send;
+
+    fn get_payment(
+        &self,
+        id: &PaymentId,
+        token: &AccessToken,
+    ) -> impl Future<Output = Result<response::PaymentDetails>> + Send;
+
+    fn refund_payment(
+        &self,
+        id: &PaymentId,
+        payload: &RefundPayment,
+        token: &AccessToken,
+    ) -> impl Future<Output = Result<response::RefundPayment>> + Send;
+
+    fn list_qrs(
+        &self,
+        query: &ListQRs,
+        token: &AccessToken,
+    ) -> impl Future<Output = Result<response::Page<response::GetQRDetails>>> + Send;
+
+    fn list_payments(
+        &self,
+        query: &ListPayments,
+        token: &AccessToken,
+    ) -> impl Future<Output = Result<response::Page<response::PaymentDetails>>> + Send;
+}
+
+impl MiaApi for Client {
+    async fn get_access_token(&self, id: &ClientId, secret: &ClientSecret) -> Result<AuthToken> {
+        return Client::get_access_token(self, id, secret).await;
+    }
+
+    async fn create_qr(
+        &self,
+        payload: &request::CreateQR<'_>,
+        token: &AccessToken,
+    ) -> Result<response::CreateQRResponse> {
+        return Client::create_qr(self, payload, token).await;
+    }
+
+    async fn get_qr(&self, qr_id: &QRId, token: &AccessToken) -> Result<response::GetQRDetails> {
+        return Client::get_qr(self, qr_id, token).await;
+    }
+
+    async fn cancel_qr(
+        &self,
+        qr_id: &QRId,
+        payload: &CancelQR,
+        token: &AccessToken,
+    ) -> Result<response::CancelQR> {
+        return Client::cancel_qr(self, qr_id, payload, token).await;
+    }
+
+    async fn get_payment(
+        &self,
+        id: &PaymentId,
+        token: &AccessToken,
+    ) -> Result<response::PaymentDetails> {
+        return Client::get_payment(self, id, token).await;
+    }
+
+    async fn refund_payment(
+        &self,
+        id: &PaymentId,
+        payload: &RefundPayment,
+        token: &AccessToken,
+    ) -> Result<response::RefundPayment> {
+        return Client::refund_payment(self, id, payload, token).await;
+    }
+
+    async fn list_qrs(
+        &self,
+        query: &ListQRs,
+        token: &AccessToken,
+    ) -> Result<response::Page<response::GetQRDetails>> {
+        return Client::list_qrs(self, query, token).await;
+    }
+
+    async fn list_payments(
+        &self,
+        query: &ListPayments,
+        token: &AccessToken,
+    ) -> Result<response::Page<response::PaymentDetails>> {
+        return Client::list_payments(self, query, token).await;
+    }
+}
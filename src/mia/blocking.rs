@@ -0,0 +1,180 @@
+//! Synchronous client for MIA API.
+//!
+//! Available with `blocking` feature.
+
+use crate::{
+    endpoint::{self, SendRequestInput},
+    error::{Error, Result},
+    models::{
+        request::{self, CancelQR, ListPayments, ListQRs, RefundPayment},
+        response::{self, AuthToken},
+        AccessToken, ClientId, ClientSecret, PaymentId, QRId,
+    },
+    telemetry::Observation,
+    token_store::{StoredToken, TokenStore},
+};
+
+/// Blocking counterpart of [crate::client::Client].
+///
+/// Must not be used from within an async runtime. Requests are always
+/// sent with `reqwest::blocking::Client`, custom transports and layers of
+/// [crate::transport], rate limiter and circuit breaker are not
+/// supported.
+#[derive(Debug)]
+pub struct Client {
+    http_client: reqwest::blocking::Client,
+    api_base_url: String,
+}
+
+impl Client {
+    pub fn new(api_base_url: String) -> Self {
+        return Self::with_http_client(api_base_url, reqwest::blocking::Client::new());
+    }
+
+    /// Client sending requests with a preconfigured `http_client`.
+    pub fn with_http_client(api_base_url: String, http_client: reqwest::blocking::Client) -> Self {
+        return Self {
+            http_client,
+            api_base_url,
+        };
+    }
+
+    /// Attempt to fetch a new [AccessToken]
+    pub fn get_access_token(&self, id: &ClientId, secret: &ClientSecret) -> Result<AuthToken> {
+        return self.send_request(endpoint::get_access_token(id, secret));
+    }
+
+    /// Access token from `store`, fetched anew and saved if it expires
+    /// soon.
+    pub fn cached_access_token(
+        &self,
+        store: &dyn TokenStore,
+        id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<AccessToken> {
+        if let Some(token) = store.load(id)?.filter(StoredToken::is_fresh) {
+            return Ok(token.access_token);
+        }
+
+        let token = StoredToken::issued(self.get_access_token(id, secret)?);
+        store.save(id, &token)?;
+
+        return Ok(token.access_token);
+    }
+
+    pub fn create_qr(
+        &self,
+        payload: &request::CreateQR<'_>,
+        token: &AccessToken,
+    ) -> Result<response::CreateQRResponse> {
+        return self.send_request(endpoint::create_qr(payload, token));
+    }
+
+    pub fn get_qr(&self, qr_id: &QRId, token: &AccessToken) -> Result<response::GetQRDetails> {
+        return self.send_request(endpoint::get_qr(qr_id, token));
+    }
+
+    pub fn cancel_qr(
+        &self,
+        qr_id: &QRId,
+        payload: &CancelQR,
+        token: &AccessToken,
+    ) -> Result<response::CancelQR> {
+        return self.send_request(endpoint::cancel_qr(qr_id, payload, token));
+    }
+
+    pub fn get_payment(
+        &self,
+        id: &PaymentId,
+        token: &AccessToken,
+    ) -> Result<response::PaymentDetails> {
+        return self.send_request(endpoint::get_payment(id, token));
+    }
+
+    pub fn refund_payment(
+        &self,
+        id: &PaymentId,
+        payload: &RefundPayment,
+        token: &AccessToken,
+    ) -> Result<response::RefundPayment> {
+        return self.send_request(endpoint::refund_payment(id, payload, token));
+    }
+
+    /// Refund payment, unless refund exceeds amount remaining in `payment`.
+    ///
+    /// See [response::PaymentDetails::check_refund].
+    pub fn refund_payment_checked(
+        &self,
+        payment: &response::PaymentDetails,
+        payload: &RefundPayment,
+        token: &AccessToken,
+    ) -> Result<response::RefundPayment> {
+        payment.check_refund(payload)?;
+        return self.refund_payment(&payment.pay_id, payload, token);
+    }
+
+    pub fn list_qrs(
+        &self,
+        query: &ListQRs,
+        token: &AccessToken,
+    ) -> Result<response::Page<response::GetQRDetails>> {
+        return self.send_request(endpoint::list_qrs(query, token));
+    }
+
+    pub fn list_payments(
+        &self,
+        query: &ListPayments,
+        token: &AccessToken,
+    ) -> Result<response::Page<response::PaymentDetails>> {
+        return self.send_request(endpoint::list_payments(query, token));
+    }
+
+    fn send_request<B, R>(&self, input: SendRequestInput<'_, B>) -> Result<R>
+    where
+        B: serde::Serialize,
+        R: serde::de::DeserializeOwned,
+    {
+        let observation = Observation::start(&input);
+
+        #[cfg(feature = "tracing")]
+        let entered = observation.span().enter();
+
+        let result = self.execute(input, &observation);
+
+        #[cfg(feature = "tracing")]
+        drop(entered);
+
+        observation.finish(&result);
+
+        return result;
+    }
+
+    fn execute<B, R>(&self, input: SendRequestInput<'_, B>, observation: &Observation) -> Result<R>
+    where
+        B: serde::Serialize,
+        R: serde::de::DeserializeOwned,
+    {
+        let url = format!("{}{}", &self.api_base_url, input.url);
+        let mut req = self
+            .http_client
+            .request(input.method.clone(), url)
+            .headers(input.headers());
+
+        if let Some(body) = input.body_bytes()? {
+            req = req.body(body);
+        }
+
+        let res = req
+            .send()
+            .map_err(|err| Error::Http(format!("error sending request: {err}")))?;
+
+        let status = res.status();
+        observation.status(status);
+
+        let body = res
+            .bytes()
+            .map_err(|err| Error::Http(format!("error reading response: {err}")))?;
+
+        return endpoint::parse_response(status, &body);
+    }
+}
@@ -0,0 +1,276 @@
+//! Circuit breaker failing requests fast while MAIB is degraded.
+//!
+//! Breaker is closed while requests succeed. Once the ratio of failed
+//! requests reaches [CircuitBreakerOptions::failure_ratio], it opens and
+//! requests fail with [Error::CircuitOpen] without being sent. After
+//! [CircuitBreakerOptions::open_for] it is half-open and lets a few probe
+//! requests through, closing again if they succeed.
+//!
+//! Failures are requests that could not be sent or read and 5xx
+//! responses. Other errors, e.g. validation errors returned by the API,
+//! are successes as far as the breaker is concerned.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent.
+    Closed,
+    /// Requests fail with [Error::CircuitOpen].
+    Open,
+    /// Limited number of probe requests is sent.
+    HalfOpen,
+}
+
+/// Change of [CircuitState], passed to [CircuitBreaker::on_transition].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub from: CircuitState,
+    pub to: CircuitState,
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerOptions {
+    /// Open once this share of requests in the window failed.
+    ///
+    /// Clamped to `(0, 1]`, i.e. `0` opens on the first failure.
+    pub failure_ratio: f64,
+
+    /// Number of most recent requests the ratio is computed over.
+    pub window: u32,
+
+    /// Do not open before this many requests are in the window.
+    pub minimum_requests: u32,
+
+    /// How long to fail fast before probing.
+    pub open_for: Duration,
+
+    /// Requests allowed at the same time while half-open.
+    ///
+    /// `0` is treated as `1`, as breaker closes only after a probe.
+    pub half_open_probes: u32,
+}
+
+impl Default for CircuitBreakerOptions {
+    fn default() -> Self {
+        return Self {
+            failure_ratio: 0.5,
+            window: 20,
+            minimum_requests: 10,
+            open_for: Duration::from_secs(30),
+            half_open_probes: 1,
+        };
+    }
+}
+
+type Callback = dyn Fn(Transition) + Send + Sync;
+
+/// Breaker shared between clones.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    options: CircuitBreakerOptions,
+    state: Arc<Mutex<State>>,
+    on_transition: Option<Arc<Callback>>,
+}
+
+impl core::fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return f
+            .debug_struct("CircuitBreaker")
+            .field("options", &self.options)
+            .field("state", &self.state())
+            .finish_non_exhaustive();
+    }
+}
+
+struct State {
+    current: CircuitState,
+    /// Outcomes of recent requests, `true` for failures.
+    outcomes: VecDeque<bool>,
+    opened_at: Instant,
+    probes: u32,
+    /// Incremented on every transition, outcomes of requests allowed
+    /// before the last transition are ignored.
+    generation: u64,
+}
+
+impl CircuitBreaker {
+    pub fn new(mut options: CircuitBreakerOptions) -> Self {
+        options.failure_ratio = match options.failure_ratio {
+            ratio if ratio.is_nan() || ratio <= 0.0 => f64::MIN_POSITIVE,
+            ratio => ratio.min(1.0),
+        };
+        options.half_open_probes = options.half_open_probes.max(1);
+
+        return Self {
+            options,
+            state: Arc::new(Mutex::new(State {
+                current: CircuitState::Closed,
+                outcomes: VecDeque::new(),
+                opened_at: Instant::now(),
+                probes: 0,
+                generation: 0,
+            })),
+            on_transition: None,
+        };
+    }
+
+    /// Call `callback` on every state change.
+    ///
+    /// Callback is called after the change, outside of any lock.
+    pub fn on_transition<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(Transition) + Send + Sync + 'static,
+    {
+        self.on_transition = Some(Arc::new(callback));
+        return self;
+    }
+
+    /// Current state, open breaker is reported half-open once
+    /// [CircuitBreakerOptions::open_for] passed.
+    pub fn state(&self) -> CircuitState {
+        let state = self.state.lock().unwrap();
+
+        if state.current == CircuitState::Open && self.open_elapsed(&state) {
+            return CircuitState::HalfOpen;
+        }
+
+        return state.current;
+    }
+
+    /// Allow a request, or fail with [Error::CircuitOpen].
+    pub(crate) fn acquire(&self) -> Result<Permit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        let mut transition = None;
+
+        if state.current == CircuitState::Open && self.open_elapsed(&state) {
+            transition = Some(self.transition(&mut state, CircuitState::HalfOpen));
+        }
+
+        let permit = match state.current {
+            CircuitState::Closed => Ok(Permit {
+                breaker: self,
+                generation: state.generation,
+            }),
+            CircuitState::HalfOpen if state.probes < self.options.half_open_probes => {
+                state.probes += 1;
+                Ok(Permit {
+                    breaker: self,
+                    generation: state.generation,
+                })
+            }
+            CircuitState::Open | CircuitState::HalfOpen => Err(Error::CircuitOpen),
+        };
+
+        drop(state);
+        self.notify(transition);
+
+        return permit;
+    }
+
+    fn record(&self, generation: u64, failed: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+
+        let mut transition = None;
+        match state.current {
+            CircuitState::HalfOpen => {
+                state.probes -= 1;
+                let to = match failed {
+                    true => CircuitState::Open,
+                    false => CircuitState::Closed,
+                };
+                transition = Some(self.transition(&mut state, to));
+            }
+            CircuitState::Closed => {
+                state.outcomes.push_back(failed);
+                while state.outcomes.len() > self.options.window as usize {
+                    state.outcomes.pop_front();
+                }
+
+                if self.should_open(&state) {
+                    transition = Some(self.transition(&mut state, CircuitState::Open));
+                }
+            }
+            CircuitState::Open => {}
+        }
+
+        drop(state);
+        self.notify(transition);
+    }
+
+    /// Release probe of a request that was dropped before completion.
+    fn release(&self, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation == generation && state.current == CircuitState::HalfOpen {
+            state.probes -= 1;
+        }
+    }
+
+    fn should_open(&self, state: &State) -> bool {
+        let total = state.outcomes.len();
+        if total == 0 || total < self.options.minimum_requests as usize {
+            return false;
+        }
+
+        let failed = state.outcomes.iter().filter(|failed| **failed).count();
+        return failed as f64 / total as f64 >= self.options.failure_ratio;
+    }
+
+    fn open_elapsed(&self, state: &State) -> bool {
+        return state.opened_at.elapsed() >= self.options.open_for;
+    }
+
+    fn transition(&self, state: &mut State, to: CircuitState) -> Transition {
+        let transition = Transition {
+            from: state.current,
+            to,
+        };
+
+        state.current = to;
+        state.generation += 1;
+        state.outcomes.clear();
+        state.probes = 0;
+        if to == CircuitState::Open {
+            state.opened_at = Instant::now();
+        }
+
+        return transition;
+    }
+
+    fn notify(&self, transition: Option<Transition>) {
+        if let (Some(callback), Some(transition)) = (&self.on_transition, transition) {
+            callback(transition);
+        }
+    }
+}
+
+/// Permission to send one request, outcome is passed to
+/// [Permit::record].
+pub(crate) struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    generation: u64,
+}
+
+impl Permit<'_> {
+    pub(crate) fn record(self, failed: bool) {
+        let this = core::mem::ManuallyDrop::new(self);
+        this.breaker.record(this.generation, failed);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.breaker.release(self.generation);
+    }
+}
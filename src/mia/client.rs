@@ -1,44 +1,134 @@
+use futures_core::Stream;
+use http::StatusCode;
+use tower::ServiceExt;
+
 use crate::{
+    circuit_breaker::CircuitBreaker,
+    endpoint::{self, SendRequestInput},
     error::{Error, Result},
+    idempotent,
     models::{
-        request::{self, CancelQR, GetAccessToken, RefundPayment},
+        request::{self, CancelQR, ListPayments, ListQRs, RefundPayment},
         response::{self, AuthToken},
         AccessToken, ClientId, ClientSecret, PaymentId, QRId,
     },
+    rate_limit::RateLimiter,
+    telemetry::Observation,
+    token_store::{StoredToken, TokenStore},
+    transport::{HttpRequest, HttpResponse, HttpTransport, Transport, TransportService},
+    wait::{self, WaitOptions},
 };
 
 #[derive(Debug)]
 pub struct Client {
-    http_client: reqwest::Client,
+    transport: Transport,
     api_base_url: String,
+    rate_limiter: Option<RateLimiter>,
+    circuit_breaker: Option<CircuitBreaker>,
 }
 
 impl Client {
+    /// Client sending requests with a default `reqwest::Client`.
+    ///
+    /// Available with `reqwest` feature, enabled by default.
+    #[cfg(feature = "reqwest")]
     pub fn new(api_base_url: String) -> Self {
+        return Self::with_transport(api_base_url, reqwest::Client::new());
+    }
+
+    /// Client sending requests with `transport` instead of
+    /// `reqwest::Client`, see [crate::transport].
+    pub fn with_transport<T: HttpTransport>(api_base_url: String, transport: T) -> Self {
+        return Self::with_service(api_base_url, TransportService::new(transport));
+    }
+
+    /// Client sending requests through `service`, see [crate::transport].
+    pub fn with_service<S>(api_base_url: String, service: S) -> Self
+    where
+        S: tower::Service<HttpRequest, Response = HttpResponse, Error = Error>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send + 'static,
+    {
         return Self {
-            http_client: reqwest::Client::new(),
+            transport: Transport::new(service),
             api_base_url,
+            rate_limiter: None,
+            circuit_breaker: None,
         };
     }
 
+    /// Wrap current transport with `layer`, see [crate::transport].
+    ///
+    /// Layers added later run first.
+    pub fn with_layer<L>(mut self, layer: L) -> Self
+    where
+        L: tower::Layer<Transport>,
+        L::Service: tower::Service<HttpRequest, Response = HttpResponse, Error = Error>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        <L::Service as tower::Service<HttpRequest>>::Future: Send + 'static,
+    {
+        self.transport = Transport::new(layer.layer(self.transport));
+        return self;
+    }
+
+    /// Delay requests to stay within rates of `limiter`.
+    ///
+    /// Requests answered with 429 are retried after `Retry-After`, see
+    /// [RateLimiter::with_max_retries].
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        return self;
+    }
+
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        return self.rate_limiter.as_ref();
+    }
+
+    /// Fail requests with [Error::CircuitOpen] while `breaker` is open.
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(breaker);
+        return self;
+    }
+
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        return self.circuit_breaker.as_ref();
+    }
+
     /// Attempt to fetch a new [AccessToken]
     pub async fn get_access_token(
         &self,
         id: &ClientId,
         secret: &ClientSecret,
     ) -> Result<AuthToken> {
-        let body = GetAccessToken {
-            client_id: id,
-            client_secret: secret,
-        };
+        return self
+            .send_request(endpoint::get_access_token(id, secret))
+            .await;
+    }
 
-        let input = SendRequestInput {
-            method: reqwest::Method::POST,
-            url: "/v2/auth/token",
-            token: None,
-            body: Some(body),
-        };
-        return self.send_request(input).await;
+    /// Access token from `store`, fetched anew and saved if it expires
+    /// soon.
+    ///
+    /// See [StoredToken::is_fresh].
+    pub async fn cached_access_token(
+        &self,
+        store: &dyn TokenStore,
+        id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<AccessToken> {
+        if let Some(token) = store.load(id)?.filter(StoredToken::is_fresh) {
+            return Ok(token.access_token);
+        }
+
+        let token = StoredToken::issued(self.get_access_token(id, secret).await?);
+        store.save(id, &token)?;
+
+        return Ok(token.access_token);
     }
 
     pub async fn create_qr<'a, 'b>(
@@ -46,13 +136,18 @@ impl Client {
         payload: &request::CreateQR<'b>,
         token: &'a AccessToken,
     ) -> Result<response::CreateQRResponse> {
-        let input = SendRequestInput {
-            method: reqwest::Method::POST,
-            url: "/v2/mia/qr",
-            token: Some(token),
-            body: Some(payload),
-        };
-        return self.send_request(input).await;
+        return self.send_request(endpoint::create_qr(payload, token)).await;
+    }
+
+    /// Create QR, unless an active QR already exists for the same order.
+    ///
+    /// See [idempotent::create_qr].
+    pub async fn create_qr_idempotent(
+        &self,
+        payload: &request::CreateQR<'_>,
+        token: &AccessToken,
+    ) -> Result<response::CreateQRResponse> {
+        return idempotent::create_qr(self, payload, token).await;
     }
 
     pub async fn get_qr(
@@ -60,15 +155,31 @@ impl Client {
         qr_id: &QRId,
         token: &AccessToken,
     ) -> Result<response::GetQRDetails> {
-        let url = format!("/v2/mia/qr/{}", qr_id.as_str());
-        let input: SendRequestInput<QRId> = SendRequestInput {
-            method: reqwest::Method::GET,
-            url: url.as_str(),
-            token: Some(token),
-            body: None,
-        };
+        return self.send_request(endpoint::get_qr(qr_id, token)).await;
+    }
 
-        return self.send_request(input).await;
+    /// Poll QR until it is paid, expired, cancelled or inactive.
+    ///
+    /// See [wait::wait_for_qr_completion].
+    pub async fn wait_for_qr_completion(
+        &self,
+        qr_id: &QRId,
+        token: &AccessToken,
+        options: WaitOptions,
+    ) -> Result<response::GetQRDetails> {
+        return wait::wait_for_qr_completion(self, qr_id, token, options).await;
+    }
+
+    /// Poll QR, yielding details every time its status changes.
+    ///
+    /// See [wait::watch_qr].
+    pub fn watch_qr<'a>(
+        &'a self,
+        qr_id: &'a QRId,
+        token: &'a AccessToken,
+        options: WaitOptions,
+    ) -> impl Stream<Item = Result<response::GetQRDetails>> + Send + 'a {
+        return wait::watch_qr(self, qr_id, token, options);
     }
 
     pub async fn cancel_qr(
@@ -77,15 +188,9 @@ impl Client {
         payload: &CancelQR,
         token: &AccessToken,
     ) -> Result<response::CancelQR> {
-        let url = format!("/v2/mia/qr/{qr_id}/cancel");
-        let input = SendRequestInput {
-            method: reqwest::Method::POST,
-            url: url.as_str(),
-            token: Some(token),
-            body: Some(payload),
-        };
-
-        return self.send_request(input).await;
+        return self
+            .send_request(endpoint::cancel_qr(qr_id, payload, token))
+            .await;
     }
 
     pub async fn get_payment(
@@ -93,15 +198,7 @@ impl Client {
         id: &PaymentId,
         token: &AccessToken,
     ) -> Result<response::PaymentDetails> {
-        let url = format!("/v2/mia/payments/{id}");
-        let input: SendRequestInput<()> = SendRequestInput {
-            method: reqwest::Method::GET,
-            url: url.as_str(),
-            token: Some(token),
-            body: None,
-        };
-
-        return self.send_request(input).await;
+        return self.send_request(endpoint::get_payment(id, token)).await;
     }
 
     pub async fn refund_payment(
@@ -110,16 +207,53 @@ impl Client {
         payload: &RefundPayment,
         token: &AccessToken,
     ) -> Result<response::RefundPayment> {
-        let url = format!("/v2/mia/payments/{id}/refund");
+        return self
+            .send_request(endpoint::refund_payment(id, payload, token))
+            .await;
+    }
 
-        let input = SendRequestInput {
-            method: reqwest::Method::POST,
-            url: &url,
-            token: Some(token),
-            body: Some(payload),
-        };
+    /// Refund payment at most once, even if the refund is retried.
+    ///
+    /// See [idempotent::refund_payment].
+    pub async fn refund_payment_idempotent(
+        &self,
+        id: &PaymentId,
+        payload: &RefundPayment,
+        refunded_before: rust_decimal::Decimal,
+        token: &AccessToken,
+    ) -> Result<response::RefundPayment> {
+        return idempotent::refund_payment(self, id, payload, refunded_before, token).await;
+    }
 
-        return self.send_request(input).await;
+    /// Refund payment, unless refund exceeds amount remaining in `payment`.
+    ///
+    /// See [response::PaymentDetails::check_refund].
+    pub async fn refund_payment_checked(
+        &self,
+        payment: &response::PaymentDetails,
+        payload: &RefundPayment,
+        token: &AccessToken,
+    ) -> Result<response::RefundPayment> {
+        payment.check_refund(payload)?;
+        return self.refund_payment(&payment.pay_id, payload, token).await;
+    }
+
+    pub async fn list_qrs(
+        &self,
+        query: &ListQRs,
+        token: &AccessToken,
+    ) -> Result<response::Page<response::GetQRDetails>> {
+        return self.send_request(endpoint::list_qrs(query, token)).await;
+    }
+
+    pub async fn list_payments(
+        &self,
+        query: &ListPayments,
+        token: &AccessToken,
+    ) -> Result<response::Page<response::PaymentDetails>> {
+        return self
+            .send_request(endpoint::list_payments(query, token))
+            .await;
     }
 
     async fn send_request<'a, B, R>(&self, input: SendRequestInput<'a, B>) -> Result<R>
@@ -127,70 +261,89 @@ impl Client {
         B: serde::Serialize,
         R: serde::de::DeserializeOwned + core::fmt::Debug,
     {
-        use reqwest::header::{self, HeaderMap, HeaderValue};
-
-        let mut headers: HeaderMap<HeaderValue> = HeaderMap::new();
-        headers.insert(
-            header::ACCEPT,
-            HeaderValue::from_str("application/json").unwrap(),
-        );
-
-        if input.method != reqwest::Method::GET {
-            headers.insert(
-                header::CONTENT_TYPE,
-                HeaderValue::from_str("application/json").unwrap(),
-            );
-        }
+        let observation = Observation::start(&input);
+        let result = self.execute(input, &observation);
 
-        if let Some(token) = input.token {
-            let value = format!("Bearer {}", token.as_str());
-            headers.insert(
-                header::AUTHORIZATION,
-                HeaderValue::from_str(&value).unwrap(),
-            );
-        }
+        #[cfg(feature = "tracing")]
+        let result = tracing::Instrument::instrument(result, observation.span().clone());
 
-        let url = format!("{}{}", &self.api_base_url, input.url);
-        let mut req = self.http_client.request(input.method, url).headers(headers);
+        let result = result.await;
+        observation.finish(&result);
 
-        if let Some(ref body) = input.body {
-            req = req.json(body);
-        }
+        return result;
+    }
 
-        let res = req
-            .send()
-            .await
-            .map_err(|err| Error::Http(format!("error sending request: {err}")))?;
+    async fn execute<B, R>(
+        &self,
+        input: SendRequestInput<'_, B>,
+        observation: &Observation,
+    ) -> Result<R>
+    where
+        B: serde::Serialize,
+        R: serde::de::DeserializeOwned,
+    {
+        let body = input.body_bytes()?;
+        let permit = match self.circuit_breaker {
+            Some(ref breaker) => Some(breaker.acquire()?),
+            None => None,
+        };
 
-        let status = res.status().as_u16();
+        let response = self.send(&input, body, observation).await;
 
-        if res.status() == 401 {
-            return Err(Error::Unauthorized);
+        if let Some(permit) = permit {
+            let failed = match response {
+                Ok((status, _)) => status.is_server_error(),
+                Err(_) => true,
+            };
+            permit.record(failed);
         }
 
-        if status >= 404 && status < 500 {
-            return Err(Error::Http(format!(
-                "we made a bad request, status: {}",
-                status
-            )));
-        }
+        let (status, body) = response?;
+        return endpoint::parse_response(status, &body);
+    }
 
-        let res: response::ApiResponse<R> = res
-            .json()
-            .await
-            .map_err(|err| Error::Json(format!("error parsing response: {err}")))?;
+    /// Send request, retrying it if throttled, and read response body.
+    async fn send<B>(
+        &self,
+        input: &SendRequestInput<'_, B>,
+        body: Option<Vec<u8>>,
+        observation: &Observation,
+    ) -> Result<(StatusCode, Vec<u8>)>
+    where
+        B: serde::Serialize,
+    {
+        let url = format!("{}{}", &self.api_base_url, input.url);
+        let mut attempt = 0;
 
-        if res.result.is_some() {
-            return Ok(res.result.unwrap());
-        }
+        let res = loop {
+            if let Some(ref limiter) = self.rate_limiter {
+                limiter.acquire(input.endpoint).await;
+            }
 
-        return Err(Error::Api(res.errors.unwrap()));
-    }
-}
+            let mut req = http::Request::builder()
+                .method(input.method.clone())
+                .uri(&url)
+                .body(body.clone().unwrap_or_default())
+                .map_err(|err| Error::Http(format!("error building request: {err}")))?;
+            *req.headers_mut() = input.headers();
 
-struct SendRequestInput<'a, B: serde::Serialize> {
-    method: reqwest::Method,
-    url: &'a str,
-    token: Option<&'a AccessToken>,
-    body: Option<B>,
+            let res = self.transport.clone().oneshot(req).await?;
+
+            match self.rate_limiter {
+                Some(ref limiter)
+                    if res.status() == StatusCode::TOO_MANY_REQUESTS
+                        && limiter.throttled(res.headers(), attempt) =>
+                {
+                    attempt += 1;
+                }
+                _ => break res,
+            }
+        };
+
+        let status = res.status();
+        observation.status(status);
+        observation.retries(attempt);
+
+        return Ok((status, res.into_body()));
+    }
 }
@@ -0,0 +1,444 @@
+//! Client configuration loaded from files and environment variables.
+//!
+//! Available with `config` feature.
+//!
+//! Files are TOML or JSON, picked by extension. Top-level keys apply to
+//! every profile, tables under `profiles` override them for a named
+//! profile:
+//!
+//! ```toml
+//! base_url = "https://api.maibmerchants.md"
+//! timeout_secs = 30
+//!
+//! [profiles.sandbox]
+//! base_url = "https://sandbox.maibmerchants.md"
+//! client_id = "..."
+//! ```
+//!
+//! Environment variables override files. Every key is read from
+//! `MAIB_<KEY>`, e.g. `MAIB_CLIENT_SECRET`, and, for the selected profile,
+//! from `MAIB_<PROFILE>_<KEY>`, e.g. `MAIB_SANDBOX_CLIENT_SECRET`. Profile
+//! is selected with [ConfigLoader::profile] or `MAIB_PROFILE`.
+//!
+//! Values of [ConfigLoader::set] override every source, values of
+//! [ConfigLoader::default_value] are used if no source sets the key.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+#[cfg(feature = "reqwest")]
+use crate::error::{Error, Result};
+use crate::models::{request::CreateQR, AccessToken, ClientId, ClientSecret, SignatureKey};
+
+/// Keys of [Settings], as used in files.
+const KEYS: &[&str] = &[
+    "base_url",
+    "client_id",
+    "client_secret",
+    "access_token",
+    "signature_key",
+    "timeout_secs",
+    "connect_timeout_secs",
+    "callback_url",
+    "redirect_url",
+];
+
+/// Validated configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Profile the configuration was loaded for.
+    pub profile: Option<String>,
+    pub base_url: String,
+    /// Set together with `client_secret`.
+    pub client_id: Option<ClientId>,
+    pub client_secret: Option<ClientSecret>,
+    /// Token to use instead of fetching one with client credentials.
+    pub access_token: Option<AccessToken>,
+    pub signature_key: Option<SignatureKey>,
+    /// Timeout of a whole request.
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// Used for QRs created without `callback_url`.
+    pub callback_url: Option<String>,
+    /// Used for QRs created without `redirect_url`.
+    pub redirect_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// File could not be read.
+    Io { path: PathBuf, message: String },
+
+    /// File is not valid TOML or JSON, or has unexpected keys.
+    Parse { path: PathBuf, message: String },
+
+    /// Profile was selected, but no file defines it.
+    UnknownProfile(String),
+
+    /// Required key is not set.
+    Missing(&'static str),
+
+    /// Key is set to an invalid value.
+    Invalid { key: &'static str, message: String },
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            ConfigError::Io { path, message } => {
+                write!(f, "can not read {}: {message}", path.display())
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "invalid config {}: {message}", path.display())
+            }
+            ConfigError::UnknownProfile(profile) => write!(f, "unknown profile {profile}"),
+            ConfigError::Missing(key) => write!(f, "{key} is not set"),
+            ConfigError::Invalid { key, message } => write!(f, "invalid {key}: {message}"),
+        };
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Loader of [Config], see [module docs](self) for sources and their
+/// precedence.
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    profile: Option<String>,
+    files: Vec<PathBuf>,
+    env_prefix: String,
+    use_env: bool,
+    defaults: HashMap<String, String>,
+    overrides: HashMap<String, String>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        return Self {
+            profile: None,
+            files: Vec::new(),
+            env_prefix: "MAIB_".to_owned(),
+            use_env: true,
+            defaults: HashMap::new(),
+            overrides: HashMap::new(),
+        };
+    }
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        return self;
+    }
+
+    /// Read file, later files override earlier ones.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push(path.into());
+        return self;
+    }
+
+    /// Prefix of environment variables, `MAIB_` by default.
+    pub fn env_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = prefix.into();
+        return self;
+    }
+
+    /// Do not read environment variables.
+    pub fn without_env(mut self) -> Self {
+        self.use_env = false;
+        return self;
+    }
+
+    /// Set `key`, as used in files, to `value` unless a file or an
+    /// environment variable sets it.
+    pub fn default_value(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.defaults.insert(key.to_owned(), value.into());
+        return self;
+    }
+
+    /// Set `key`, as used in files, to `value` overriding files and
+    /// environment variables, e.g. for command-line flags.
+    pub fn set(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.overrides.insert(key.to_owned(), value.into());
+        return self;
+    }
+
+    pub fn load(&self) -> core::result::Result<Config, ConfigError> {
+        return self.load_with_env(|name| std::env::var(name).ok());
+    }
+
+    /// Load with environment variables looked up by `env`.
+    pub fn load_with_env<F>(&self, env: F) -> core::result::Result<Config, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let env = |name: &str| {
+            let value = self.use_env.then(|| env(name)).flatten()?;
+            return Some(value).filter(|value| !value.is_empty());
+        };
+
+        let profile = self
+            .profile
+            .clone()
+            .or_else(|| env(&format!("{}PROFILE", self.env_prefix)));
+
+        let mut settings = Settings::from_values(self.defaults.clone())?;
+        let mut profile_found = false;
+
+        for path in &self.files {
+            let file = ConfigFile::read(path)?;
+            settings.merge(file.settings);
+
+            if let Some(ref profile) = profile {
+                if let Some(overrides) = file.profiles.get(profile) {
+                    settings.merge(overrides.clone());
+                    profile_found = true;
+                }
+            }
+        }
+
+        if let Some(ref profile) = profile {
+            if !self.files.is_empty() && !profile_found {
+                return Err(ConfigError::UnknownProfile(profile.clone()));
+            }
+        }
+
+        let mut prefixes = vec![self.env_prefix.clone()];
+        if let Some(ref profile) = profile {
+            prefixes.push(format!("{}{}_", self.env_prefix, profile.to_uppercase()));
+        }
+
+        for prefix in prefixes {
+            let values = KEYS
+                .iter()
+                .filter_map(|key| {
+                    let value = env(&format!("{prefix}{}", key.to_uppercase()))?;
+                    return Some((key.to_string(), value));
+                })
+                .collect();
+            settings.merge(Settings::from_values(values)?);
+        }
+        settings.merge(Settings::from_values(self.overrides.clone())?);
+
+        return settings.validate(profile);
+    }
+}
+
+impl Config {
+    /// Client sending requests with configured timeouts.
+    #[cfg(feature = "reqwest")]
+    pub fn client(&self) -> Result<crate::client::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        let http_client = builder
+            .build()
+            .map_err(|err| Error::Http(format!("error building client: {err}")))?;
+
+        return Ok(crate::client::Client::with_transport(
+            self.base_url.clone(),
+            http_client,
+        ));
+    }
+
+    /// Blocking client sending requests with configured timeouts.
+    #[cfg(feature = "blocking")]
+    pub fn blocking_client(&self) -> Result<crate::blocking::Client> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        let http_client = builder
+            .build()
+            .map_err(|err| Error::Http(format!("error building client: {err}")))?;
+
+        return Ok(crate::blocking::Client::with_http_client(
+            self.base_url.clone(),
+            http_client,
+        ));
+    }
+
+    /// Set `callback_url` and `redirect_url` of `payload` that are empty
+    /// to configured defaults.
+    pub fn apply_defaults(&self, payload: &mut CreateQR<'_>) {
+        if let (true, Some(url)) = (payload.callback_url.is_empty(), &self.callback_url) {
+            payload.callback_url = url.clone();
+        }
+
+        if let (true, Some(url)) = (payload.redirect_url.is_empty(), &self.redirect_url) {
+            payload.redirect_url = url.clone();
+        }
+    }
+}
+
+/// Unvalidated values of a single source.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Settings {
+    base_url: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    access_token: Option<String>,
+    signature_key: Option<String>,
+    timeout_secs: Option<u64>,
+    connect_timeout_secs: Option<u64>,
+    callback_url: Option<String>,
+    redirect_url: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct ConfigFile {
+    settings: Settings,
+    profiles: HashMap<String, Settings>,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> core::result::Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|err| ConfigError::Io {
+            path: path.to_owned(),
+            message: err.to_string(),
+        })?;
+
+        let parse_error = |message: String| ConfigError::Parse {
+            path: path.to_owned(),
+            message,
+        };
+
+        let parsed = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents).map_err(|err| err.to_string()),
+            Some("toml") => toml::from_str(&contents).map_err(|err| err.to_string()),
+            _ => Err("expected .toml or .json file".to_owned()),
+        };
+        let mut table: serde_json::Map<String, serde_json::Value> = parsed.map_err(parse_error)?;
+
+        // Profiles are split off first, as unknown keys are rejected.
+        let profiles = match table.remove("profiles") {
+            Some(profiles) => {
+                serde_json::from_value(profiles).map_err(|err| parse_error(err.to_string()))?
+            }
+            None => HashMap::new(),
+        };
+        let settings = serde_json::from_value(serde_json::Value::Object(table))
+            .map_err(|err| parse_error(err.to_string()))?;
+
+        return Ok(Self { settings, profiles });
+    }
+}
+
+impl Settings {
+    fn from_values(mut values: HashMap<String, String>) -> core::result::Result<Self, ConfigError> {
+        let mut take = |key: &str| values.remove(key);
+
+        return Ok(Self {
+            base_url: take("base_url"),
+            client_id: take("client_id"),
+            client_secret: take("client_secret"),
+            access_token: take("access_token"),
+            signature_key: take("signature_key"),
+            timeout_secs: parse_secs("timeout_secs", take("timeout_secs"))?,
+            connect_timeout_secs: parse_secs("connect_timeout_secs", take("connect_timeout_secs"))?,
+            callback_url: take("callback_url"),
+            redirect_url: take("redirect_url"),
+        });
+    }
+
+    fn merge(&mut self, other: Settings) {
+        self.base_url = other.base_url.or(self.base_url.take());
+        self.client_id = other.client_id.or(self.client_id.take());
+        self.client_secret = other.client_secret.or(self.client_secret.take());
+        self.access_token = other.access_token.or(self.access_token.take());
+        self.signature_key = other.signature_key.or(self.signature_key.take());
+        self.timeout_secs = other.timeout_secs.or(self.timeout_secs);
+        self.connect_timeout_secs = other.connect_timeout_secs.or(self.connect_timeout_secs);
+        self.callback_url = other.callback_url.or(self.callback_url.take());
+        self.redirect_url = other.redirect_url.or(self.redirect_url.take());
+    }
+
+    fn validate(self, profile: Option<String>) -> core::result::Result<Config, ConfigError> {
+        let base_url = self.base_url.ok_or(ConfigError::Missing("base_url"))?;
+        validate_url("base_url", &base_url)?;
+        let base_url = base_url.trim_end_matches('/').to_owned();
+
+        match (&self.client_id, &self.client_secret) {
+            (Some(_), None) => return Err(ConfigError::Missing("client_secret")),
+            (None, Some(_)) => return Err(ConfigError::Missing("client_id")),
+            _ => {}
+        }
+
+        for (key, url) in [
+            ("callback_url", &self.callback_url),
+            ("redirect_url", &self.redirect_url),
+        ] {
+            if let Some(url) = url {
+                validate_url(key, url)?;
+            }
+        }
+
+        return Ok(Config {
+            profile,
+            base_url,
+            client_id: self.client_id.map(ClientId::new),
+            client_secret: self.client_secret.map(ClientSecret::new),
+            access_token: self.access_token.map(AccessToken::new),
+            signature_key: self.signature_key.map(SignatureKey::from),
+            timeout: duration("timeout_secs", self.timeout_secs)?,
+            connect_timeout: duration("connect_timeout_secs", self.connect_timeout_secs)?,
+            callback_url: self.callback_url,
+            redirect_url: self.redirect_url,
+        });
+    }
+}
+
+fn parse_secs(
+    key: &'static str,
+    value: Option<String>,
+) -> core::result::Result<Option<u64>, ConfigError> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    return value.parse().map(Some).map_err(|err| ConfigError::Invalid {
+        key,
+        message: format!("{err}"),
+    });
+}
+
+fn duration(
+    key: &'static str,
+    secs: Option<u64>,
+) -> core::result::Result<Option<Duration>, ConfigError> {
+    return match secs {
+        Some(0) => Err(ConfigError::Invalid {
+            key,
+            message: "must be positive".to_owned(),
+        }),
+        secs => Ok(secs.map(Duration::from_secs)),
+    };
+}
+
+fn validate_url(key: &'static str, url: &str) -> core::result::Result<(), ConfigError> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        return Ok(());
+    }
+
+    return Err(ConfigError::Invalid {
+        key,
+        message: format!("{url} is not an http(s) url"),
+    });
+}
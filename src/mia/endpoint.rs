@@ -0,0 +1,217 @@
+//! Request building and response parsing shared by all clients.
+
+use http::{
+    header::{self, HeaderMap, HeaderValue},
+    Method, StatusCode,
+};
+
+use crate::{
+    error::{Error, Result},
+    models::{
+        request::{self, CancelQR, GetAccessToken, ListPayments, ListQRs, RefundPayment},
+        response, AccessToken, ClientId, ClientSecret, PaymentId, QRId,
+    },
+};
+
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub(crate) struct SendRequestInput<'a, B: serde::Serialize> {
+    pub(crate) method: Method,
+    /// Path template of the endpoint, e.g. `/v2/mia/qr/{qr_id}`.
+    pub(crate) endpoint: &'static str,
+    pub(crate) url: String,
+    pub(crate) qr_id: Option<&'a QRId>,
+    pub(crate) pay_id: Option<&'a PaymentId>,
+    pub(crate) token: Option<&'a AccessToken>,
+    pub(crate) body: Option<B>,
+}
+
+impl<B: serde::Serialize> SendRequestInput<'_, B> {
+    pub(crate) fn headers(&self) -> HeaderMap<HeaderValue> {
+        let mut headers: HeaderMap<HeaderValue> = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+
+        if self.method != Method::GET {
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+        }
+
+        if let Some(token) = self.token {
+            let value = format!("Bearer {}", token.as_str());
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&value).unwrap(),
+            );
+        }
+
+        return headers;
+    }
+
+    pub(crate) fn body_bytes(&self) -> Result<Option<Vec<u8>>> {
+        let Some(ref body) = self.body else {
+            return Ok(None);
+        };
+
+        let bytes = serde_json::to_vec(body)
+            .map_err(|err| Error::Json(format!("error serializing request: {err}")))?;
+
+        return Ok(Some(bytes));
+    }
+}
+
+pub(crate) fn get_access_token<'a>(
+    id: &'a ClientId,
+    secret: &'a ClientSecret,
+) -> SendRequestInput<'a, GetAccessToken<'a>> {
+    return SendRequestInput {
+        method: Method::POST,
+        endpoint: "/v2/auth/token",
+        url: "/v2/auth/token".to_owned(),
+        qr_id: None,
+        pay_id: None,
+        token: None,
+        body: Some(GetAccessToken {
+            client_id: id,
+            client_secret: secret,
+        }),
+    };
+}
+
+pub(crate) fn create_qr<'a, 'b>(
+    payload: &'a request::CreateQR<'b>,
+    token: &'a AccessToken,
+) -> SendRequestInput<'a, &'a request::CreateQR<'b>> {
+    return SendRequestInput {
+        method: Method::POST,
+        endpoint: "/v2/mia/qr",
+        url: "/v2/mia/qr".to_owned(),
+        qr_id: None,
+        pay_id: None,
+        token: Some(token),
+        body: Some(payload),
+    };
+}
+
+pub(crate) fn get_qr<'a>(qr_id: &'a QRId, token: &'a AccessToken) -> SendRequestInput<'a, ()> {
+    return SendRequestInput {
+        method: Method::GET,
+        endpoint: "/v2/mia/qr/{qr_id}",
+        url: format!("/v2/mia/qr/{qr_id}"),
+        qr_id: Some(qr_id),
+        pay_id: None,
+        token: Some(token),
+        body: None,
+    };
+}
+
+pub(crate) fn cancel_qr<'a>(
+    qr_id: &'a QRId,
+    payload: &'a CancelQR,
+    token: &'a AccessToken,
+) -> SendRequestInput<'a, &'a CancelQR> {
+    return SendRequestInput {
+        method: Method::POST,
+        endpoint: "/v2/mia/qr/{qr_id}/cancel",
+        url: format!("/v2/mia/qr/{qr_id}/cancel"),
+        qr_id: Some(qr_id),
+        pay_id: None,
+        token: Some(token),
+        body: Some(payload),
+    };
+}
+
+pub(crate) fn get_payment<'a>(
+    id: &'a PaymentId,
+    token: &'a AccessToken,
+) -> SendRequestInput<'a, ()> {
+    return SendRequestInput {
+        method: Method::GET,
+        endpoint: "/v2/mia/payments/{pay_id}",
+        url: format!("/v2/mia/payments/{id}"),
+        qr_id: None,
+        pay_id: Some(id),
+        token: Some(token),
+        body: None,
+    };
+}
+
+pub(crate) fn refund_payment<'a>(
+    id: &'a PaymentId,
+    payload: &'a RefundPayment,
+    token: &'a AccessToken,
+) -> SendRequestInput<'a, &'a RefundPayment> {
+    return SendRequestInput {
+        method: Method::POST,
+        endpoint: "/v2/mia/payments/{pay_id}/refund",
+        url: format!("/v2/mia/payments/{id}/refund"),
+        qr_id: None,
+        pay_id: Some(id),
+        token: Some(token),
+        body: Some(payload),
+    };
+}
+
+pub(crate) fn list_qrs<'a>(query: &ListQRs, token: &'a AccessToken) -> SendRequestInput<'a, ()> {
+    return SendRequestInput {
+        method: Method::GET,
+        endpoint: "/v2/mia/qr",
+        url: with_query("/v2/mia/qr", query),
+        qr_id: None,
+        pay_id: None,
+        token: Some(token),
+        body: None,
+    };
+}
+
+pub(crate) fn list_payments<'a>(
+    query: &ListPayments,
+    token: &'a AccessToken,
+) -> SendRequestInput<'a, ()> {
+    return SendRequestInput {
+        method: Method::GET,
+        endpoint: "/v2/mia/payments",
+        url: with_query("/v2/mia/payments", query),
+        qr_id: None,
+        pay_id: None,
+        token: Some(token),
+        body: None,
+    };
+}
+
+fn with_query<Q: serde::Serialize>(path: &str, query: &Q) -> String {
+    let query = serde_urlencoded::to_string(query).expect("list filters are flat structs");
+
+    if query.is_empty() {
+        return path.to_owned();
+    }
+
+    return format!("{path}?{query}");
+}
+
+/// Turn raw API response into a result.
+pub(crate) fn parse_response<R>(status: StatusCode, body: &[u8]) -> Result<R>
+where
+    R: serde::de::DeserializeOwned,
+{
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(Error::Unauthorized);
+    }
+
+    let status = status.as_u16();
+    if (404..500).contains(&status) {
+        return Err(Error::Http(format!(
+            "we made a bad request, status: {}",
+            status
+        )));
+    }
+
+    let res: response::ApiResponse<R> = serde_json::from_slice(body)
+        .map_err(|err| Error::Json(format!("error parsing response: {err}")))?;
+
+    if let Some(result) = res.result {
+        return Ok(result);
+    }
+
+    return Err(Error::Api(res.errors.unwrap_or_default()));
+}
@@ -1,6 +1,11 @@
 pub type Result<T> = core::result::Result<T, Error>;
 
-#[derive(Debug)]
+/// Error of any operation of this crate.
+///
+/// New variants may be added, e.g. by optional features, so matches
+/// outside of this crate need a wildcard arm.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     /// Access token has expired or was not set.
     Unauthorized,
@@ -13,9 +18,75 @@ pub enum Error {
 
     /// API server responded with errors.
     Api(Vec<ApiError>),
+
+    /// Active QR with the same order id exists, but with different parameters.
+    Conflict(crate::models::QRId),
+
+    /// Refund was refused before calling the API, as it is not positive
+    /// or exceeds amount remaining after previous refunds.
+    RefundExceedsRemaining {
+        requested: rust_decimal::Decimal,
+        remaining: rust_decimal::Decimal,
+    },
+
+    /// Refund was refused before calling the API, as refunded amount of
+    /// the payment is neither the amount refunded before the refund, nor
+    /// that amount plus the refund.
+    RefundedAmountMismatch {
+        expected: rust_decimal::Decimal,
+        refunded: rust_decimal::Decimal,
+    },
+
+    /// Operation did not complete in time.
+    Timeout,
+
+    /// Operation was cancelled by caller.
+    Cancelled,
+
+    /// Request was not sent, as circuit breaker is open.
+    CircuitOpen,
+
+    /// No merchant is registered under this key.
+    UnknownMerchant(String),
+
+    /// Token could not be read from or written to a token store.
+    TokenStore(String),
+
+    /// Notification could not be read from or written to a notification
+    /// store.
+    NotificationStore(String),
+
+    /// QR code could not be rendered.
+    #[cfg(feature = "render")]
+    Render(String),
+}
+
+impl Error {
+    /// Short name of the error kind.
+    ///
+    /// Unlike the error message, this is safe to report in logs and metrics.
+    pub fn kind(&self) -> &'static str {
+        return match self {
+            Error::Unauthorized => "unauthorized",
+            Error::Http(_) => "http",
+            Error::Json(_) => "json",
+            Error::Api(_) => "api",
+            Error::Conflict(_) => "conflict",
+            Error::RefundExceedsRemaining { .. } => "refund_exceeds_remaining",
+            Error::RefundedAmountMismatch { .. } => "refunded_amount_mismatch",
+            Error::Timeout => "timeout",
+            Error::Cancelled => "cancelled",
+            Error::CircuitOpen => "circuit_open",
+            Error::UnknownMerchant(_) => "unknown_merchant",
+            Error::TokenStore(_) => "token_store",
+            Error::NotificationStore(_) => "notification_store",
+            #[cfg(feature = "render")]
+            Error::Render(_) => "render",
+        };
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ApiError {
     error_code: String,
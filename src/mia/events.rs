@@ -0,0 +1,222 @@
+//! Typed events of verified notifications, dispatched to async handlers.
+//!
+//! Events are derived from `qrStatus` of a notification, see
+//! [Event::of]. Refunds are reported to `callback_url` of the refund
+//! rather than of the QR, [Event::RefundCompleted] is dispatched for
+//! these callbacks by
+//! [Webhook::handle_refund](crate::webhook::Webhook::handle_refund).
+//!
+//! Handlers of a notification run one after another and the first error
+//! stops the rest. A [HandlerError::Retryable] error makes
+//! [Webhook::handle](crate::webhook::Webhook::handle) answer MAIB with a
+//! status it redelivers the notification on, so handlers should be
+//! idempotent.
+
+use std::{collections::HashMap, sync::Arc};
+
+use futures_core::future::BoxFuture;
+
+use crate::models::{
+    Notification, QRStatus, ValidSignatureNotification, ValidSignatureRefundNotification,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// Payment was executed, reported for every paid QR, including
+    /// static QRs which stay active.
+    PaymentExecuted,
+
+    /// QR was paid and can not be paid again.
+    QrPaid,
+
+    /// QR expired or was cancelled before being paid.
+    QrExpired,
+
+    /// Payment was refunded, fully or partially, see
+    /// [EventDispatcher::on_refund_completed].
+    RefundCompleted,
+}
+
+impl Event {
+    /// Events reported by `notification`, in the order handlers run.
+    pub fn of(notification: &Notification) -> Vec<Event> {
+        return match notification.qr_status() {
+            QRStatus::Active => vec![Event::PaymentExecuted],
+            QRStatus::Paid => vec![Event::PaymentExecuted, Event::QrPaid],
+            QRStatus::Expired | QRStatus::Cancelled => vec![Event::QrExpired],
+            _ => vec![],
+        };
+    }
+}
+
+/// Reason a handler failed to process a notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandlerError {
+    /// Processing may succeed later, e.g. database is unavailable, MAIB
+    /// should deliver the notification again.
+    Retryable(String),
+
+    /// Processing will not succeed on redelivery, e.g. order is unknown.
+    Permanent(String),
+}
+
+impl HandlerError {
+    pub fn retryable(err: impl core::fmt::Display) -> Self {
+        return HandlerError::Retryable(err.to_string());
+    }
+
+    pub fn permanent(err: impl core::fmt::Display) -> Self {
+        return HandlerError::Permanent(err.to_string());
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        return matches!(self, HandlerError::Retryable(_));
+    }
+}
+
+impl core::fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            HandlerError::Retryable(message) => write!(f, "retryable: {message}"),
+            HandlerError::Permanent(message) => write!(f, "permanent: {message}"),
+        };
+    }
+}
+
+impl std::error::Error for HandlerError {}
+
+type Handler = dyn Fn(ValidSignatureNotification) -> BoxFuture<'static, Result<(), HandlerError>>
+    + Send
+    + Sync;
+
+type RefundHandler = dyn Fn(ValidSignatureRefundNotification) -> BoxFuture<'static, Result<(), HandlerError>>
+    + Send
+    + Sync;
+
+/// Handlers registered per [Event].
+#[derive(Clone, Default)]
+pub struct EventDispatcher {
+    handlers: HashMap<Event, Vec<Arc<Handler>>>,
+    refund_handlers: Vec<Arc<RefundHandler>>,
+}
+
+impl core::fmt::Debug for EventDispatcher {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let counts: HashMap<&Event, usize> = self
+            .handlers
+            .iter()
+            .map(|(event, handlers)| (event, handlers.len()))
+            .collect();
+
+        return f
+            .debug_struct("EventDispatcher")
+            .field("handlers", &counts)
+            .field("refund_handlers", &self.refund_handlers.len())
+            .finish();
+    }
+}
+
+impl EventDispatcher {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Run `handler` on `event`, after handlers registered before.
+    ///
+    /// # Panics
+    ///
+    /// If `event` is [Event::RefundCompleted], its handlers take a refund
+    /// notification, see [EventDispatcher::on_refund_completed].
+    pub fn on<F, Fut>(mut self, event: Event, handler: F) -> Self
+    where
+        F: Fn(ValidSignatureNotification) -> Fut + Send + Sync + 'static,
+        Fut: core::future::Future<Output = Result<(), HandlerError>> + Send + 'static,
+    {
+        assert_ne!(
+            event,
+            Event::RefundCompleted,
+            "refund handlers are registered with on_refund_completed"
+        );
+
+        let handler: Arc<Handler> = Arc::new(move |notification| Box::pin(handler(notification)));
+        self.handlers.entry(event).or_default().push(handler);
+        return self;
+    }
+
+    pub fn on_payment_executed<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(ValidSignatureNotification) -> Fut + Send + Sync + 'static,
+        Fut: core::future::Future<Output = Result<(), HandlerError>> + Send + 'static,
+    {
+        return self.on(Event::PaymentExecuted, handler);
+    }
+
+    pub fn on_qr_paid<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(ValidSignatureNotification) -> Fut + Send + Sync + 'static,
+        Fut: core::future::Future<Output = Result<(), HandlerError>> + Send + 'static,
+    {
+        return self.on(Event::QrPaid, handler);
+    }
+
+    pub fn on_qr_expired<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(ValidSignatureNotification) -> Fut + Send + Sync + 'static,
+        Fut: core::future::Future<Output = Result<(), HandlerError>> + Send + 'static,
+    {
+        return self.on(Event::QrExpired, handler);
+    }
+
+    /// Run `handler` on refund callbacks, see
+    /// [Webhook::handle_refund](crate::webhook::Webhook::handle_refund).
+    pub fn on_refund_completed<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(ValidSignatureRefundNotification) -> Fut + Send + Sync + 'static,
+        Fut: core::future::Future<Output = Result<(), HandlerError>> + Send + 'static,
+    {
+        let handler: Arc<RefundHandler> =
+            Arc::new(move |notification| Box::pin(handler(notification)));
+        self.refund_handlers.push(handler);
+        return self;
+    }
+
+    /// Run handlers of every event of `notification`, see [Event::of].
+    pub async fn dispatch(
+        &self,
+        notification: &ValidSignatureNotification,
+    ) -> Result<(), HandlerError> {
+        for event in Event::of(&notification.0) {
+            self.dispatch_event(event, notification).await?;
+        }
+
+        return Ok(());
+    }
+
+    /// Run handlers of `event` only.
+    pub async fn dispatch_event(
+        &self,
+        event: Event,
+        notification: &ValidSignatureNotification,
+    ) -> Result<(), HandlerError> {
+        let Some(handlers) = self.handlers.get(&event) else {
+            return Ok(());
+        };
+
+        for handler in handlers {
+            handler(notification.clone()).await?;
+        }
+
+        return Ok(());
+    }
+    /// Run handlers of [Event::RefundCompleted].
+    pub async fn dispatch_refund(
+        &self,
+        notification: &ValidSignatureRefundNotification,
+    ) -> Result<(), HandlerError> {
+        for handler in &self.refund_handlers {
+            handler(notification.clone()).await?;
+        }
+
+        return Ok(());
+    }
+}
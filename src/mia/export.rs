@@ -0,0 +1,282 @@
+//! Export of payments and QRs to CSV and JSON Lines.
+//!
+//! Available with `export` feature.
+//!
+//! Columns are listed in [Record::COLUMNS] and only ever appended to,
+//! JSON Lines objects use the same names as keys, in the same order.
+//! Absent values are empty in CSV and `null` in JSON Lines.
+//!
+//! CSV values starting with `=`, `+`, `-`, `@`, tab or carriage return
+//! are prefixed with `'`, so spreadsheets do not evaluate them as
+//! formulas, see [neutralize_formula]. Numbers are written as is.
+
+use std::{borrow::Cow, io::Write, marker::PhantomData};
+
+use crate::models::response::{GetQRDetails, PaymentDetails};
+
+/// Value written instead of masked characters.
+const MASK: char = '*';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Comma separated values with a header row.
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Mask payer IBAN and name, see [mask_iban] and [mask_name].
+    pub mask_pii: bool,
+}
+
+/// Type that can be exported as a row.
+pub trait Record {
+    const COLUMNS: &'static [&'static str];
+
+    /// Values in order of [Record::COLUMNS].
+    fn row(&self, options: &ExportOptions) -> Vec<Option<String>>;
+}
+
+/// Writer of records, rows are written as soon as they are passed in.
+///
+/// Pages of a list can be written as they are fetched, without keeping
+/// the whole range in memory.
+pub struct Exporter<W: Write, T: Record> {
+    inner: Inner<W>,
+    options: ExportOptions,
+    header_written: bool,
+    _record: PhantomData<fn(&T)>,
+}
+
+enum Inner<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    JsonLines(W),
+}
+
+impl<W: Write, T: Record> Exporter<W, T> {
+    pub fn new(writer: W, format: Format, options: ExportOptions) -> Self {
+        let inner = match format {
+            Format::Csv => Inner::Csv(Box::new(csv::Writer::from_writer(writer))),
+            Format::JsonLines => Inner::JsonLines(writer),
+        };
+
+        return Self {
+            inner,
+            options,
+            header_written: false,
+            _record: PhantomData,
+        };
+    }
+
+    pub fn write(&mut self, record: &T) -> std::io::Result<()> {
+        let row = record.row(&self.options);
+
+        match self.inner {
+            Inner::Csv(ref mut writer) => {
+                if !self.header_written {
+                    writer.write_record(T::COLUMNS)?;
+                    self.header_written = true;
+                }
+
+                let row: Vec<Cow<'_, str>> = row
+                    .iter()
+                    .map(|value| neutralize_formula(value.as_deref().unwrap_or("")))
+                    .collect();
+                writer.write_record(row.iter().map(|value| value.as_bytes()))?;
+            }
+            Inner::JsonLines(ref mut writer) => {
+                let object = JsonRow {
+                    columns: T::COLUMNS,
+                    values: &row,
+                };
+
+                serde_json::to_writer(&mut *writer, &object)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn write_all<'a, I>(&mut self, records: I) -> std::io::Result<()>
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        for record in records {
+            self.write(record)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Flush buffered rows and return the underlying writer.
+    ///
+    /// CSV header is written even if there were no records.
+    pub fn finish(self) -> std::io::Result<W> {
+        return match self.inner {
+            Inner::Csv(mut writer) => {
+                if !self.header_written {
+                    writer.write_record(T::COLUMNS)?;
+                }
+
+                writer.into_inner().map_err(|err| err.into_error())
+            }
+            Inner::JsonLines(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+        };
+    }
+}
+
+/// JSON object with keys in order of columns.
+struct JsonRow<'a> {
+    columns: &'static [&'static str],
+    values: &'a [Option<String>],
+}
+
+impl serde::Serialize for JsonRow<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, value) in self.columns.iter().zip(self.values) {
+            map.serialize_entry(column, value)?;
+        }
+
+        return map.end();
+    }
+}
+
+/// Prefix `value` with `'` if a spreadsheet would evaluate it as a
+/// formula, unless it is a number.
+pub fn neutralize_formula(value: &str) -> Cow<'_, str> {
+    let formula = value.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    if !formula || value.parse::<rust_decimal::Decimal>().is_ok() {
+        return Cow::Borrowed(value);
+    }
+
+    return Cow::Owned(format!("'{value}"));
+}
+
+/// Keep country code, check digits and last 4 characters of IBAN.
+pub fn mask_iban(iban: &str) -> String {
+    let count = iban.chars().count();
+    if count <= 8 {
+        return MASK.to_string().repeat(count);
+    }
+
+    return iban
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i < 4 || i >= count - 4 { c } else { MASK })
+        .collect();
+}
+
+/// Keep first letter of every word of a name.
+pub fn mask_name(name: &str) -> String {
+    return name
+        .split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().map(String::from).unwrap_or_default();
+            let masked: String = chars.map(|_| MASK).collect();
+            return format!("{first}{masked}");
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+}
+
+impl Record for PaymentDetails {
+    const COLUMNS: &'static [&'static str] = &[
+        "pay_id",
+        "reference_id",
+        "qr_id",
+        "extension_id",
+        "order_id",
+        "amount",
+        "commission",
+        "currency",
+        "description",
+        "payer_name",
+        "payer_iban",
+        "status",
+        "executed_at",
+        "refunded_at",
+        "refunded_amount",
+        "terminal_id",
+    ];
+
+    fn row(&self, options: &ExportOptions) -> Vec<Option<String>> {
+        let (payer_name, payer_iban) = match options.mask_pii {
+            true => (mask_name(&self.payer_name), mask_iban(&self.payer_iban)),
+            false => (self.payer_name.clone(), self.payer_iban.clone()),
+        };
+
+        return vec![
+            Some(self.pay_id.to_string()),
+            Some(self.reference_id.clone()),
+            Some(self.qr_id.to_string()),
+            self.extension_id.as_ref().map(ToString::to_string),
+            self.order_id.clone(),
+            Some(self.amount.to_string()),
+            Some(self.commission.to_string()),
+            Some(self.currency.to_string()),
+            Some(self.description.clone()),
+            Some(payer_name),
+            Some(payer_iban),
+            Some(self.status.to_string()),
+            Some(self.executed_at.clone()),
+            self.refunded_at.clone(),
+            self.refunded_amount.map(|amount| amount.to_string()),
+            self.terminal_id.clone(),
+        ];
+    }
+}
+
+impl Record for GetQRDetails {
+    const COLUMNS: &'static [&'static str] = &[
+        "qr_id",
+        "order_id",
+        "type",
+        "status",
+        "amount_type",
+        "amount",
+        "amount_min",
+        "amount_max",
+        "currency",
+        "description",
+        "url",
+        "callback_url",
+        "redirect_url",
+        "terminal_id",
+        "created_at",
+        "updated_at",
+        "expires_at",
+    ];
+
+    fn row(&self, _options: &ExportOptions) -> Vec<Option<String>> {
+        return vec![
+            Some(self.qr_id.to_string()),
+            self.order_id.clone(),
+            Some(self.r#type.to_string()),
+            Some(self.status.to_string()),
+            Some(self.amount_type.to_string()),
+            Some(self.amount.to_string()),
+            self.amount_min.map(|amount| amount.to_string()),
+            self.amount_max.map(|amount| amount.to_string()),
+            Some(self.currency.to_string()),
+            Some(self.description.clone()),
+            Some(self.url.clone()),
+            Some(self.callback_url.clone()),
+            Some(self.redirect_url.clone()),
+            Some(self.terminal_id.clone()),
+            Some(self.created_at.to_rfc3339()),
+            Some(self.updated_at.to_rfc3339()),
+            Some(self.expires_at.to_rfc3339()),
+        ];
+    }
+}
@@ -0,0 +1,144 @@
+//! Operations that are safe to retry after a timeout.
+
+use rust_decimal::Decimal;
+
+use crate::{
+    api::MiaApi,
+    error::{Error, Result},
+    models::{
+        request::{CreateQR, ListQRs, RefundPayment},
+        response::{self, CreateQRResponse, GetQRDetails},
+        AccessToken, PaymentId, QRStatus,
+    },
+};
+
+/// Create QR, unless an active QR already exists for the same order.
+///
+/// Existing QR is returned if its parameters match `payload`, otherwise
+/// [Error::Conflict] is returned. `expires_at` is not compared, as it is
+/// usually computed anew on retry.
+///
+/// Payload without `order_id` can not be deduplicated and is always
+/// created.
+pub async fn create_qr<A: MiaApi>(
+    api: &A,
+    payload: &CreateQR<'_>,
+    token: &AccessToken,
+) -> Result<CreateQRResponse> {
+    let Some(order_id) = payload.order_id else {
+        return api.create_qr(payload, token).await;
+    };
+
+    let query = ListQRs {
+        order_id: Some(order_id.to_owned()),
+        status: Some(QRStatus::Active),
+        ..Default::default()
+    };
+    let existing = api.list_qrs(&query, token).await?;
+
+    let Some(qr) = existing.items.into_iter().next() else {
+        return api.create_qr(payload, token).await;
+    };
+
+    if !same_parameters(&qr, payload) {
+        return Err(Error::Conflict(qr.qr_id));
+    }
+
+    return Ok(CreateQRResponse {
+        qr_id: qr.qr_id,
+        order_id: qr.order_id,
+        r#type: qr.r#type,
+        url: qr.url,
+        expires_at: qr.expires_at.to_rfc3339(),
+    });
+}
+
+/// Refund payment at most once, even if the refund is retried.
+///
+/// `refunded_before` is the amount refunded from the payment before
+/// this refund, as recorded by the caller when the refund was first
+/// attempted, zero if the payment was never refunded. Payment is read
+/// first and compared with it:
+/// - refunded amount is still `refunded_before`, the payment is refunded,
+/// - refunded amount is `refunded_before` plus the requested amount, the
+///   refund was already made and is not repeated, `refund_id` of the
+///   response is not set,
+/// - otherwise [Error::RefundedAmountMismatch] is returned, as the
+///   payment was refunded by someone else in the meantime.
+///
+/// If MAIB refuses the refund with [Error::Api], e.g. as a concurrent
+/// attempt refunded the payment first, the payment is read again and
+/// the refund is reported as made if the refunded amount is
+/// `refunded_before` plus the requested amount. Attempts running at the
+/// same time can still both refund a partial amount, so they should be
+/// serialized by the caller, e.g. by a lock of the job.
+///
+/// Amount of a full refund is what remained after `refunded_before`.
+pub async fn refund_payment<A: MiaApi>(
+    api: &A,
+    id: &PaymentId,
+    payload: &RefundPayment,
+    refunded_before: Decimal,
+    token: &AccessToken,
+) -> Result<response::RefundPayment> {
+    let payment = api.get_payment(id, token).await?;
+    let refunded = payment.amount - payment.remaining_amount();
+    let requested = payload.amount.unwrap_or(payment.amount - refunded_before);
+
+    if refunded == refunded_before {
+        payment.check_refund(payload)?;
+
+        let err = match api.refund_payment(id, payload, token).await {
+            Err(err @ Error::Api(_)) => err,
+            result => return result,
+        };
+
+        let payment = api.get_payment(id, token).await?;
+        if payment.amount - payment.remaining_amount() == refunded_before + requested {
+            return Ok(refund_made(id, &payment, requested));
+        }
+
+        return Err(err);
+    }
+
+    if refunded == refunded_before + requested {
+        return Ok(refund_made(id, &payment, requested));
+    }
+
+    return Err(Error::RefundedAmountMismatch {
+        expected: refunded_before,
+        refunded,
+    });
+}
+
+/// Response of a refund made by an earlier attempt.
+fn refund_made(
+    id: &PaymentId,
+    payment: &response::PaymentDetails,
+    amount: Decimal,
+) -> response::RefundPayment {
+    return response::RefundPayment {
+        pay_id: id.clone(),
+        status: payment.status.clone(),
+        refund_id: None,
+        amount: Some(amount),
+    };
+}
+
+fn same_parameters(qr: &GetQRDetails, payload: &CreateQR<'_>) -> bool {
+    let terminal_matches = payload
+        .terminal_id
+        .as_ref()
+        .is_none_or(|terminal_id| *terminal_id == qr.terminal_id);
+
+    return qr.r#type == payload.r#type
+        && qr.amount_type == payload.amount_type
+        && qr.amount == payload.amount
+        && qr.amount_min == payload.amount_min
+        && qr.amount_max == payload.amount_max
+        && qr.currency == payload.currency
+        && qr.description == payload.description
+        && qr.callback_url == payload.callback_url
+        && qr.redirect_url == payload.redirect_url
+        && terminal_matches;
+}
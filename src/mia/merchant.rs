@@ -0,0 +1,256 @@
+//! Several merchants, each with its own credentials, behind one client.
+//!
+//! Merchants are registered under a key chosen by the caller, e.g. name
+//! of the legal entity. Access tokens are fetched on first use and cached
+//! per merchant until shortly before they expire.
+//!
+//! Incoming notifications are matched to a merchant by `terminalId`, or
+//! by a segment of the callback url, so QRs of every merchant should be
+//! created with `callback_url` ending with its key, e.g.
+//! `https://shop.md/maib/callback/{key}`.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    client::Client,
+    error::{Error, Result},
+    models::{
+        request::{self, CancelQR, ListPayments, ListQRs, RefundPayment},
+        response, AccessToken, ClientId, ClientSecret, NotificationPayload, PaymentId, QRId,
+        SignatureKey, ValidSignatureNotification,
+    },
+};
+
+/// Tokens are refreshed this long before they expire.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct MerchantCredentials {
+    pub client_id: ClientId,
+    pub client_secret: ClientSecret,
+    pub signature_key: SignatureKey,
+    /// Terminals notifications of this merchant are sent for.
+    pub terminal_ids: Vec<String>,
+}
+
+/// Credentials and cached tokens of several merchants.
+#[derive(Debug)]
+pub struct MerchantRegistry {
+    client: Client,
+    merchants: HashMap<String, Merchant>,
+}
+
+#[derive(Debug)]
+struct Merchant {
+    credentials: MerchantCredentials,
+    token: tokio::sync::Mutex<Option<CachedToken>>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    token: AccessToken,
+    expires_at: Instant,
+}
+
+impl MerchantRegistry {
+    pub fn new(client: Client) -> Self {
+        return Self {
+            client,
+            merchants: HashMap::new(),
+        };
+    }
+
+    pub fn with_merchant(
+        mut self,
+        key: impl Into<String>,
+        credentials: MerchantCredentials,
+    ) -> Self {
+        let merchant = Merchant {
+            credentials,
+            token: tokio::sync::Mutex::new(None),
+        };
+        self.merchants.insert(key.into(), merchant);
+        return self;
+    }
+
+    pub fn client(&self) -> &Client {
+        return &self.client;
+    }
+
+    /// Merchant registered under `key`, or [Error::UnknownMerchant].
+    pub fn merchant(&self, key: &str) -> Result<MerchantClient<'_>> {
+        let Some(merchant) = self.merchants.get(key) else {
+            return Err(Error::UnknownMerchant(key.to_owned()));
+        };
+
+        return Ok(MerchantClient {
+            client: &self.client,
+            merchant,
+        });
+    }
+
+    /// Keys of registered merchants, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        return self.merchants.keys().map(String::as_str);
+    }
+
+    /// Find merchant `payload` was sent for and validate its signature.
+    ///
+    /// Merchant is picked by terminal id of the notification, falling
+    /// back to `callback_segment`, i.e. merchant key taken from the
+    /// callback url the notification was received on. Returns [None] if
+    /// no merchant matches, or signature is not valid for the matched
+    /// merchant.
+    pub fn verify_notification(
+        &self,
+        payload: NotificationPayload,
+        callback_segment: Option<&str>,
+    ) -> Option<(&str, ValidSignatureNotification)> {
+        let (key, merchant) = self.route(&payload, callback_segment)?;
+        let notification =
+            payload.validate_signature(merchant.credentials.signature_key.clone())?;
+
+        return Some((key, notification));
+    }
+
+    fn route(
+        &self,
+        payload: &NotificationPayload,
+        callback_segment: Option<&str>,
+    ) -> Option<(&str, &Merchant)> {
+        if let Some(ref terminal_id) = payload.notification().terminal_id {
+            let by_terminal = self
+                .merchants
+                .iter()
+                .find(|(_, merchant)| merchant.credentials.terminal_ids.contains(terminal_id));
+
+            if let Some((key, merchant)) = by_terminal {
+                return Some((key.as_str(), merchant));
+            }
+        }
+
+        let (key, merchant) = self.merchants.get_key_value(callback_segment?)?;
+        return Some((key.as_str(), merchant));
+    }
+}
+
+/// API calls on behalf of a single merchant.
+///
+/// Calls are made with the cached access token of the merchant. A call
+/// failing with [Error::Unauthorized] is retried once with a new token.
+#[derive(Debug, Clone, Copy)]
+pub struct MerchantClient<'a> {
+    client: &'a Client,
+    merchant: &'a Merchant,
+}
+
+impl MerchantClient<'_> {
+    pub fn credentials(&self) -> &MerchantCredentials {
+        return &self.merchant.credentials;
+    }
+
+    /// Cached access token, fetched anew if it expires soon.
+    pub async fn access_token(&self) -> Result<AccessToken> {
+        let mut cached = self.merchant.token.lock().await;
+
+        if let Some(ref token) = *cached {
+            if token.expires_at > Instant::now() + EXPIRY_MARGIN {
+                return Ok(token.token.clone());
+            }
+        }
+
+        let credentials = &self.merchant.credentials;
+        let auth = self
+            .client
+            .get_access_token(&credentials.client_id, &credentials.client_secret)
+            .await?;
+
+        let token = CachedToken {
+            expires_at: auth.expires_in() + Instant::now(),
+            token: auth.take_access_token(),
+        };
+        *cached = Some(token.clone());
+
+        return Ok(token.token);
+    }
+
+    /// Drop cached token, next call fetches a new one.
+    pub async fn invalidate_token(&self) {
+        *self.merchant.token.lock().await = None;
+    }
+
+    pub async fn create_qr(
+        &self,
+        payload: &request::CreateQR<'_>,
+    ) -> Result<response::CreateQRResponse> {
+        return self
+            .call(|token| async move { self.client.create_qr(payload, &token).await })
+            .await;
+    }
+
+    pub async fn get_qr(&self, qr_id: &QRId) -> Result<response::GetQRDetails> {
+        return self
+            .call(|token| async move { self.client.get_qr(qr_id, &token).await })
+            .await;
+    }
+
+    pub async fn cancel_qr(&self, qr_id: &QRId, payload: &CancelQR) -> Result<response::CancelQR> {
+        return self
+            .call(|token| async move { self.client.cancel_qr(qr_id, payload, &token).await })
+            .await;
+    }
+
+    pub async fn list_qrs(
+        &self,
+        query: &ListQRs,
+    ) -> Result<response::Page<response::GetQRDetails>> {
+        return self
+            .call(|token| async move { self.client.list_qrs(query, &token).await })
+            .await;
+    }
+
+    pub async fn get_payment(&self, id: &PaymentId) -> Result<response::PaymentDetails> {
+        return self
+            .call(|token| async move { self.client.get_payment(id, &token).await })
+            .await;
+    }
+
+    pub async fn refund_payment(
+        &self,
+        id: &PaymentId,
+        payload: &RefundPayment,
+    ) -> Result<response::RefundPayment> {
+        return self
+            .call(|token| async move { self.client.refund_payment(id, payload, &token).await })
+            .await;
+    }
+
+    pub async fn list_payments(
+        &self,
+        query: &ListPayments,
+    ) -> Result<response::Page<response::PaymentDetails>> {
+        return self
+            .call(|token| async move { self.client.list_payments(query, &token).await })
+            .await;
+    }
+
+    async fn call<F, Fut, R>(&self, request: F) -> Result<R>
+    where
+        F: Fn(AccessToken) -> Fut,
+        Fut: core::future::Future<Output = Result<R>>,
+    {
+        let token = self.access_token().await?;
+        let result = request(token).await;
+
+        if !matches!(result, Err(Error::Unauthorized)) {
+            return result;
+        }
+
+        self.invalidate_token().await;
+        let token = self.access_token().await?;
+        return request(token).await;
+    }
+}
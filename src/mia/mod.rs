@@ -1,3 +1,32 @@
+pub mod api;
+pub mod circuit_breaker;
 pub mod client;
+pub(crate) mod endpoint;
 pub mod error;
+pub mod events;
+pub mod idempotent;
+pub mod merchant;
 pub mod models;
+pub mod notification_store;
+pub mod rate_limit;
+pub mod reconcile;
+pub(crate) mod telemetry;
+pub mod token_store;
+pub mod transport;
+pub mod wait;
+pub mod webhook;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+#[cfg(feature = "export")]
+pub mod export;
+
+#[cfg(feature = "render")]
+pub mod render;
+
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use rust_decimal::Decimal;
 use sha2::Digest;
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct ClientId(String);
 
 impl ClientId {
@@ -18,7 +18,7 @@ impl core::fmt::Display for ClientId {
     }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct ClientSecret(String);
 
 impl ClientSecret {
@@ -33,7 +33,7 @@ impl core::fmt::Display for ClientSecret {
     }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct AccessToken(pub(crate) String);
 
 impl AccessToken {
@@ -53,7 +53,7 @@ impl core::fmt::Display for AccessToken {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct AccessTokenDuration(core::time::Duration);
 
 impl AccessTokenDuration {
@@ -76,7 +76,7 @@ impl core::ops::Add<std::time::Instant> for AccessTokenDuration {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct QRId(String);
 
 impl QRId {
@@ -101,7 +101,7 @@ impl core::fmt::Display for QRId {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
 pub struct Signature(String);
 
 impl Signature {
@@ -136,7 +136,7 @@ impl From<String> for SignatureKey {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
 pub struct ExtensionId(String);
 
 impl ExtensionId {
@@ -155,7 +155,7 @@ impl core::fmt::Display for ExtensionId {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
 pub struct PaymentId(String);
 
 impl PaymentId {
@@ -174,41 +174,124 @@ impl core::fmt::Display for PaymentId {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub enum PaymentType {
-    Fixed,
-    Controlled,
-    Free,
+/// Defines an enum of string values returned by the API.
+///
+/// Values unknown to this crate are kept in `Unknown` variant,
+/// so new values introduced by MAIB do not break deserialization
+/// and are preserved when serialized back.
+macro_rules! api_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident => $value:literal,
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )*
+
+            /// Value not known to this version of the crate.
+            Unknown(String),
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => value.as_str(),
+                }
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    other => $name::Unknown(other.to_owned()),
+                }
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                match value.as_str() {
+                    $($value => $name::$variant,)*
+                    _ => $name::Unknown(value),
+                }
+            }
+        }
+
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                return write!(f, "{}", self.as_str());
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> core::result::Result<S::Ok, S::Error> {
+                return serializer.serialize_str(self.as_str());
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> core::result::Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+                return Ok($name::from(value));
+            }
+        }
+    };
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub enum PaymentStatus {
-    Executed,
-    Refunded,
+api_enum! {
+    pub enum PaymentType {
+        Fixed => "Fixed",
+        Controlled => "Controlled",
+        Free => "Free",
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
-pub enum TokenType {
-    Bearer,
+api_enum! {
+    pub enum PaymentStatus {
+        Executed => "Executed",
+        Refunded => "Refunded",
+        PartiallyRefunded => "PartiallyRefunded",
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub enum QRType {
-    /// QR payment that can be paid
-    /// more than once.
-    Static,
+api_enum! {
+    pub enum TokenType {
+        Bearer => "Bearer",
+    }
+}
 
-    /// QR payment that can be paid once.
-    Dynamic,
+api_enum! {
+    pub enum QRType {
+        /// QR payment that can be paid
+        /// more than once.
+        Static => "Static",
 
-    /// QR payment can pe paid more than once.
-    ///
-    /// This also allows to modify amount and expiration date
-    /// while is considere valid payment.
-    Hybrid,
+        /// QR payment that can be paid once.
+        Dynamic => "Dynamic",
+
+        /// QR payment can pe paid more than once.
+        ///
+        /// This also allows to modify amount and expiration date
+        /// while is considere valid payment.
+        Hybrid => "Hybrid",
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -236,29 +319,27 @@ impl core::fmt::Display for Currency {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub enum QRStatus {
-    Active,
-    Inactive,
-    Expired,
-    Paid,
-    Cancelled,
+api_enum! {
+    pub enum QRStatus {
+        Active => "Active",
+        Inactive => "Inactive",
+        Expired => "Expired",
+        Paid => "Paid",
+        Cancelled => "Cancelled",
+    }
 }
 
-impl core::fmt::Display for QRStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            QRStatus::Active => write!(f, "Active"),
-            QRStatus::Inactive => write!(f, "Inactive"),
-            QRStatus::Expired => write!(f, "Expired"),
-            QRStatus::Paid => write!(f, "Paid"),
-            QRStatus::Cancelled => write!(f, "Cancelled"),
-        }
+impl QRStatus {
+    /// Whether QR can no longer change its status.
+    pub fn is_final(&self) -> bool {
+        return matches!(
+            self,
+            QRStatus::Paid | QRStatus::Expired | QRStatus::Cancelled | QRStatus::Inactive
+        );
     }
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Notification {
     pub(crate) amount: Decimal,
@@ -280,12 +361,21 @@ impl Notification {
     pub fn pay_id(&self) -> &PaymentId {
         &self.pay_id
     }
+
+    pub fn qr_status(&self) -> &QRStatus {
+        &self.qr_status
+    }
+
+    /// Date time the payment was executed at, RFC 3339 formatted.
+    pub fn executed_at(&self) -> &str {
+        &self.executed_at
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ValidSignatureNotification(pub Notification);
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NotificationPayload {
     pub(crate) result: Notification,
@@ -294,8 +384,6 @@ pub struct NotificationPayload {
 
 impl NotificationPayload {
     pub(crate) fn build_signature(&self, key: SignatureKey) -> Signature {
-        use base64::prelude::*;
-
         let n = &self.result;
         let mut this_signature = format!(
             "{}:{}:{}:{}:{}",
@@ -315,13 +403,7 @@ impl NotificationPayload {
             this_signature = format!("{this_signature}:{terminal_id}");
         }
 
-        this_signature = format!("{this_signature}:{}", key.0);
-
-        let sig_sha256 = sha2::Sha256::digest(&this_signature);
-        let encoded = hex::encode(sig_sha256);
-        let signature = Signature::new(BASE64_STANDARD.encode(encoded));
-
-        return signature;
+        return sign(this_signature, &key);
     }
 
     /// Attempt to validate signature with provided key.
@@ -329,8 +411,10 @@ impl NotificationPayload {
     /// If it is not valid, this will return [None].
     pub fn validate_signature(self, key: SignatureKey) -> Option<ValidSignatureNotification> {
         let signature = self.build_signature(key);
+        let valid = signature.eq(&self.signature);
+        crate::telemetry::webhook_verified(valid);
 
-        if signature.eq(&self.signature) {
+        if valid {
             return Some(ValidSignatureNotification(self.result));
         }
 
@@ -342,19 +426,129 @@ impl NotificationPayload {
     }
 }
 
+/// Signature of `values`, joined with `:` in the order of their keys.
+fn sign(values: String, key: &SignatureKey) -> Signature {
+    use base64::prelude::*;
+
+    let sig_sha256 = sha2::Sha256::digest(format!("{values}:{}", key.0));
+    let encoded = hex::encode(sig_sha256);
+
+    return Signature::new(BASE64_STANDARD.encode(encoded));
+}
+
+/// Refund status sent to `callback_url` of a refund, see
+/// [request::RefundPayment].
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefundNotification {
+    pub(crate) amount: Decimal,
+    pub(crate) pay_id: PaymentId,
+    pub(crate) refund_id: String,
+    pub(crate) status: PaymentStatus,
+}
+
+impl RefundNotification {
+    /// Refunded amount.
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn pay_id(&self) -> &PaymentId {
+        &self.pay_id
+    }
+
+    pub fn refund_id(&self) -> &str {
+        &self.refund_id
+    }
+
+    /// Payment status after the refund.
+    pub fn status(&self) -> &PaymentStatus {
+        &self.status
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidSignatureRefundNotification(pub RefundNotification);
+
+/// Refund callback, signed like [NotificationPayload].
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefundNotificationPayload {
+    pub(crate) result: RefundNotification,
+    pub(crate) signature: Signature,
+}
+
+impl RefundNotificationPayload {
+    pub(crate) fn build_signature(&self, key: SignatureKey) -> Signature {
+        let n = &self.result;
+        let values = format!("{}:{}:{}:{}", n.amount, n.pay_id, n.refund_id, n.status);
+
+        return sign(values, &key);
+    }
+
+    /// Attempt to validate signature with provided key.
+    ///
+    /// If it is not valid, this will return [None].
+    pub fn validate_signature(self, key: SignatureKey) -> Option<ValidSignatureRefundNotification> {
+        let signature = self.build_signature(key);
+        let valid = signature.eq(&self.signature);
+        crate::telemetry::webhook_verified(valid);
+
+        if valid {
+            return Some(ValidSignatureRefundNotification(self.result));
+        }
+
+        return None;
+    }
+
+    pub fn notification(&self) -> &RefundNotification {
+        &self.result
+    }
+}
+
 pub mod request {
+    use chrono::{DateTime, Utc};
     use rust_decimal::Decimal;
 
-    use super::{ClientId, ClientSecret, Currency, PaymentType, QRType};
+    use super::{
+        ClientId, ClientSecret, Currency, PaymentId, PaymentStatus, PaymentType, QRId, QRStatus,
+        QRType,
+    };
 
-    #[derive(Debug, serde::Serialize)]
+    #[derive(Debug, Clone, PartialEq, serde::Serialize)]
     #[serde(rename_all = "camelCase")]
     pub struct GetAccessToken<'a> {
         pub client_id: &'a ClientId,
         pub client_secret: &'a ClientSecret,
     }
 
-    #[derive(Debug, serde::Serialize)]
+    impl GetAccessToken<'_> {
+        pub fn to_owned_request(&self) -> OwnedGetAccessToken {
+            return OwnedGetAccessToken {
+                client_id: self.client_id.clone(),
+                client_secret: self.client_secret.clone(),
+            };
+        }
+    }
+
+    /// Owned version of [GetAccessToken].
+    #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct OwnedGetAccessToken {
+        pub client_id: ClientId,
+        pub client_secret: ClientSecret,
+    }
+
+    impl OwnedGetAccessToken {
+        pub fn as_request(&self) -> GetAccessToken<'_> {
+            return GetAccessToken {
+                client_id: &self.client_id,
+                client_secret: &self.client_secret,
+            };
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize)]
     #[serde(rename_all = "camelCase")]
     pub struct CreateQR<'a> {
         pub r#type: super::QRType,
@@ -399,21 +593,163 @@ pub mod request {
                 terminal_id: None,
             };
         }
+
+        pub fn to_owned_request(&self) -> OwnedCreateQR {
+            return OwnedCreateQR {
+                r#type: self.r#type.clone(),
+                expires_at: self.expires_at.map(str::to_owned),
+                amount_type: self.amount_type.clone(),
+                amount: self.amount,
+                amount_min: self.amount_min,
+                amount_max: self.amount_max,
+                currency: self.currency,
+                description: self.description.clone(),
+                order_id: self.order_id.map(str::to_owned),
+                callback_url: self.callback_url.clone(),
+                redirect_url: self.redirect_url.clone(),
+                terminal_id: self.terminal_id.clone(),
+            };
+        }
+    }
+
+    /// Owned version of [CreateQR].
+    ///
+    /// Can be stored and (de)serialized, use [OwnedCreateQR::as_request]
+    /// to send it.
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct OwnedCreateQR {
+        pub r#type: super::QRType,
+        /// Date time when Dynamic QR expires.
+        ///
+        /// Must be a valid ISO 8601-1:2019 value.
+        pub expires_at: Option<String>,
+        pub amount_type: super::PaymentType,
+
+        pub amount: rust_decimal::Decimal,
+        pub amount_min: Option<rust_decimal::Decimal>,
+        pub amount_max: Option<rust_decimal::Decimal>,
+
+        pub currency: super::Currency,
+        pub description: String,
+        pub order_id: Option<String>,
+        pub callback_url: String,
+        pub redirect_url: String,
+        pub terminal_id: Option<String>,
+    }
+
+    impl OwnedCreateQR {
+        pub fn as_request(&self) -> CreateQR<'_> {
+            return CreateQR {
+                r#type: self.r#type.clone(),
+                expires_at: self.expires_at.as_deref(),
+                amount_type: self.amount_type.clone(),
+                amount: self.amount,
+                amount_min: self.amount_min,
+                amount_max: self.amount_max,
+                currency: self.currency,
+                description: self.description.clone(),
+                order_id: self.order_id.as_deref(),
+                callback_url: self.callback_url.clone(),
+                redirect_url: self.redirect_url.clone(),
+                terminal_id: self.terminal_id.clone(),
+            };
+        }
     }
 
     #[derive(Debug, serde::Serialize)]
     pub struct QRExpiresAt<'a>(&'a str);
 
-    #[derive(Debug, serde::Serialize)]
+    #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct CancelQR {
         pub reason: String,
     }
 
-    #[derive(Debug, serde::Serialize)]
+    #[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct RefundPayment {
         pub reason: String,
+
+        /// Amount to refund, whole remaining amount is refunded if not set.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub amount: Option<Decimal>,
+
+        /// Url notified about refund status.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub callback_url: Option<String>,
+    }
+
+    impl RefundPayment {
+        /// Refund whole remaining amount.
+        pub fn full(reason: String) -> Self {
+            return Self {
+                reason,
+                amount: None,
+                callback_url: None,
+            };
+        }
+
+        /// Refund only `amount`.
+        pub fn partial(amount: Decimal, reason: String) -> Self {
+            return Self {
+                reason,
+                amount: Some(amount),
+                callback_url: None,
+            };
+        }
+    }
+
+    /// Filters of QR list, sent as query parameters.
+    ///
+    /// Unset filters are not sent.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ListQRs {
+        /// Maximum number of items to return.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub count: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub offset: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub qr_id: Option<QRId>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub order_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub r#type: Option<QRType>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub status: Option<QRStatus>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub terminal_id: Option<String>,
+    }
+
+    /// Filters of payment list, sent as query parameters.
+    ///
+    /// Unset filters are not sent.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ListPayments {
+        /// Maximum number of items to return.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub count: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub offset: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub pay_id: Option<PaymentId>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub qr_id: Option<QRId>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub order_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub status: Option<PaymentStatus>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub terminal_id: Option<String>,
+        /// Only payments executed at or after this moment.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub executed_at_from: Option<DateTime<Utc>>,
+        /// Only payments executed before this moment.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub executed_at_to: Option<DateTime<Utc>>,
     }
 }
 
@@ -443,7 +779,7 @@ pub mod response {
         }
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct AuthToken {
         access_token: super::AccessToken,
@@ -465,12 +801,12 @@ pub mod response {
             self.access_token
         }
 
-        pub fn token_type(&self) -> super::TokenType {
-            self.token_type
+        pub fn token_type(&self) -> &super::TokenType {
+            &self.token_type
         }
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct CreateQRResponse {
         pub qr_id: super::QRId,
@@ -480,7 +816,7 @@ pub mod response {
         pub expires_at: String,
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct GetQRDetails {
         pub qr_id: super::QRId,
@@ -502,14 +838,14 @@ pub mod response {
         pub expires_at: DateTime<Utc>,
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct CancelQR {
         pub qr_id: super::QRId,
         pub status: super::QRStatus,
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct PaymentDetails {
         pub pay_id: PaymentId,
@@ -526,13 +862,60 @@ pub mod response {
         pub status: PaymentStatus,
         pub executed_at: String,
         pub refunded_at: Option<String>,
+        /// Sum of all refunds, absent if payment was never refunded.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub refunded_amount: Option<Decimal>,
         pub terminal_id: Option<String>,
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    impl PaymentDetails {
+        /// Amount that can still be refunded.
+        pub fn remaining_amount(&self) -> Decimal {
+            return match (&self.status, self.refunded_amount) {
+                (PaymentStatus::Refunded, None) => Decimal::ZERO,
+                (_, Some(refunded)) => (self.amount - refunded).max(Decimal::ZERO),
+                (_, None) => self.amount,
+            };
+        }
+
+        /// Check that refund does not exceed [PaymentDetails::remaining_amount].
+        pub fn check_refund(
+            &self,
+            payload: &super::request::RefundPayment,
+        ) -> crate::error::Result<()> {
+            let remaining = self.remaining_amount();
+            let requested = payload.amount.unwrap_or(remaining);
+
+            if requested <= Decimal::ZERO || requested > remaining {
+                return Err(crate::error::Error::RefundExceedsRemaining {
+                    requested,
+                    remaining,
+                });
+            }
+
+            return Ok(());
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct RefundPayment {
         pub pay_id: PaymentId,
+        /// Payment status after the refund.
         pub status: PaymentStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub refund_id: Option<String>,
+        /// Refunded amount.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub amount: Option<Decimal>,
+    }
+
+    /// Single page of a list, see `offset` and `count` of list requests.
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Page<T> {
+        pub items: Vec<T>,
+        /// Number of items matching filters, across all pages.
+        pub total_count: u64,
     }
 }
@@ -0,0 +1,192 @@
+//! Notifications already received, to detect redeliveries and replays.
+//!
+//! MAIB can deliver the same notification more than once, and a captured
+//! payload keeps a valid signature forever. Notifications are recorded
+//! by `payId` and `qrStatus`, so a notification for the same payment
+//! with the same status is a duplicate, see
+//! [Webhook](crate::webhook::Webhook).
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    error::Result,
+    models::{PaymentId, QRStatus},
+};
+
+/// Storage of received notifications.
+///
+/// Methods are blocking and expected to be quick, e.g. a query of a
+/// local database.
+pub trait NotificationStore: Send + Sync {
+    /// Record notification for `pay_id` with `qr_status`.
+    ///
+    /// Returns `false` if it was recorded before.
+    fn record(&self, pay_id: &PaymentId, qr_status: &QRStatus) -> Result<bool>;
+
+    /// Whether notification for `pay_id` with `qr_status` was recorded.
+    fn contains(&self, pay_id: &PaymentId, qr_status: &QRStatus) -> Result<bool>;
+
+    /// Forget notifications recorded before `before`.
+    fn prune(&self, before: DateTime<Utc>) -> Result<()>;
+}
+
+/// Shared store, e.g. to prune it while used by a webhook.
+impl<T: NotificationStore + ?Sized> NotificationStore for Arc<T> {
+    fn record(&self, pay_id: &PaymentId, qr_status: &QRStatus) -> Result<bool> {
+        return (**self).record(pay_id, qr_status);
+    }
+
+    fn contains(&self, pay_id: &PaymentId, qr_status: &QRStatus) -> Result<bool> {
+        return (**self).contains(pay_id, qr_status);
+    }
+
+    fn prune(&self, before: DateTime<Utc>) -> Result<()> {
+        return (**self).prune(before);
+    }
+}
+
+/// Store keeping notifications for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct InMemoryNotificationStore {
+    received: Mutex<HashMap<(PaymentId, QRStatus), DateTime<Utc>>>,
+}
+
+impl InMemoryNotificationStore {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+}
+
+impl NotificationStore for InMemoryNotificationStore {
+    fn record(&self, pay_id: &PaymentId, qr_status: &QRStatus) -> Result<bool> {
+        let mut received = self.received.lock().unwrap();
+        let key = (pay_id.clone(), qr_status.clone());
+
+        if received.contains_key(&key) {
+            return Ok(false);
+        }
+
+        received.insert(key, Utc::now());
+        return Ok(true);
+    }
+
+    fn contains(&self, pay_id: &PaymentId, qr_status: &QRStatus) -> Result<bool> {
+        let received = self.received.lock().unwrap();
+        return Ok(received.contains_key(&(pay_id.clone(), qr_status.clone())));
+    }
+
+    fn prune(&self, before: DateTime<Utc>) -> Result<()> {
+        let mut received = self.received.lock().unwrap();
+        received.retain(|_, received_at| *received_at >= before);
+        return Ok(());
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteNotificationStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::{path::Path, sync::Mutex};
+
+    use chrono::{DateTime, Utc};
+    use rusqlite::Connection;
+
+    use super::NotificationStore;
+    use crate::{
+        error::{Error, Result},
+        models::{PaymentId, QRStatus},
+    };
+
+    /// Store keeping notifications in `maib_notifications` table of a
+    /// SQLite database.
+    ///
+    /// Table is created if it does not exist. Database can be shared by
+    /// several processes receiving notifications.
+    #[derive(Debug)]
+    pub struct SqliteNotificationStore {
+        connection: Mutex<Connection>,
+    }
+
+    impl SqliteNotificationStore {
+        /// Open or create database at `path`.
+        pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+            let connection = Connection::open(path).map_err(error)?;
+            return Self::with_connection(connection);
+        }
+
+        /// Store using an already opened database.
+        pub fn with_connection(connection: Connection) -> Result<Self> {
+            connection
+                .execute_batch(
+                    "CREATE TABLE IF NOT EXISTS maib_notifications (
+                        pay_id TEXT NOT NULL,
+                        qr_status TEXT NOT NULL,
+                        received_at INTEGER NOT NULL,
+                        PRIMARY KEY (pay_id, qr_status)
+                    )",
+                )
+                .map_err(error)?;
+
+            return Ok(Self {
+                connection: Mutex::new(connection),
+            });
+        }
+    }
+
+    impl NotificationStore for SqliteNotificationStore {
+        fn record(&self, pay_id: &PaymentId, qr_status: &QRStatus) -> Result<bool> {
+            let inserted = self
+                .connection
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT OR IGNORE INTO maib_notifications (pay_id, qr_status, received_at)
+                    VALUES (?1, ?2, ?3)",
+                    (pay_id.as_str(), qr_status.as_str(), Utc::now().timestamp()),
+                )
+                .map_err(error)?;
+
+            return Ok(inserted == 1);
+        }
+
+        fn contains(&self, pay_id: &PaymentId, qr_status: &QRStatus) -> Result<bool> {
+            let found = self
+                .connection
+                .lock()
+                .unwrap()
+                .query_row(
+                    "SELECT EXISTS (
+                        SELECT 1 FROM maib_notifications WHERE pay_id = ?1 AND qr_status = ?2
+                    )",
+                    (pay_id.as_str(), qr_status.as_str()),
+                    |row| row.get(0),
+                )
+                .map_err(error)?;
+
+            return Ok(found);
+        }
+
+        fn prune(&self, before: DateTime<Utc>) -> Result<()> {
+            self.connection
+                .lock()
+                .unwrap()
+                .execute(
+                    "DELETE FROM maib_notifications WHERE received_at < ?1",
+                    (before.timestamp(),),
+                )
+                .map_err(error)?;
+
+            return Ok(());
+        }
+    }
+
+    fn error(err: rusqlite::Error) -> Error {
+        return Error::NotificationStore(format!("sqlite: {err}"));
+    }
+}
@@ -0,0 +1,300 @@
+//! Client-side rate limiting of API requests.
+//!
+//! Requests over the configured rate are delayed, not failed. Limiter is
+//! shared between clones, so one limiter can be passed to several clients
+//! talking to the same merchant.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use http::header::{HeaderMap, RETRY_AFTER};
+use tokio::time::Instant;
+
+/// Delay used when 429 response has no valid `Retry-After` header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Longer `Retry-After` delays are shortened to this.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Number of requests allowed per period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl Rate {
+    pub fn per_second(requests: u32) -> Self {
+        return Self {
+            requests,
+            per: Duration::from_secs(1),
+        };
+    }
+
+    pub fn per_minute(requests: u32) -> Self {
+        return Self {
+            requests,
+            per: Duration::from_secs(60),
+        };
+    }
+
+    /// Time it takes to refill one request.
+    fn interval(&self) -> Duration {
+        return self.per / self.requests.max(1);
+    }
+}
+
+/// Global and per-endpoint token buckets.
+///
+/// Every bucket starts full, i.e. allows a burst of `requests` of its
+/// [Rate]. Endpoints are named by their path template, e.g.
+/// `/v2/mia/qr/{qr_id}`.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    global: Option<Mutex<Bucket>>,
+    endpoints: HashMap<&'static str, Mutex<Bucket>>,
+    max_retries: u32,
+    /// Requests are not sent before this, set from `Retry-After`.
+    paused_until: Mutex<Option<Instant>>,
+    queued: AtomicUsize,
+}
+
+/// Current state of the limiter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Budget {
+    /// Requests that can be sent without delay, [None] if there is no
+    /// global limit.
+    pub global: Option<u32>,
+    /// Same as `global`, per limited endpoint.
+    pub endpoints: HashMap<&'static str, u32>,
+    /// Time left until requests are resumed after a 429 response.
+    pub paused_for: Option<Duration>,
+}
+
+impl RateLimiter {
+    /// Limiter without a global limit.
+    pub fn new() -> Self {
+        return Self {
+            inner: Arc::new(Inner {
+                global: None,
+                endpoints: HashMap::new(),
+                max_retries: 3,
+                paused_until: Mutex::new(None),
+                queued: AtomicUsize::new(0),
+            }),
+        };
+    }
+
+    /// Limiter allowing `rate` requests over all endpoints.
+    pub fn global(rate: Rate) -> Self {
+        return Self::new().with_global(rate);
+    }
+
+    pub fn with_global(mut self, rate: Rate) -> Self {
+        self.configure().global = Some(Mutex::new(Bucket::new(rate)));
+        return self;
+    }
+
+    /// Additionally limit requests to `endpoint`, e.g. `/v2/mia/qr`.
+    pub fn with_endpoint(mut self, endpoint: &'static str, rate: Rate) -> Self {
+        self.configure()
+            .endpoints
+            .insert(endpoint, Mutex::new(Bucket::new(rate)));
+        return self;
+    }
+
+    /// How many times a request answered with 429 is retried, 3 by default.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.configure().max_retries = max_retries;
+        return self;
+    }
+
+    pub fn budget(&self) -> Budget {
+        let now = Instant::now();
+        let available = |bucket: &Mutex<Bucket>| bucket.lock().unwrap().available(now);
+
+        return Budget {
+            global: self.inner.global.as_ref().map(available),
+            endpoints: self
+                .inner
+                .endpoints
+                .iter()
+                .map(|(endpoint, bucket)| (*endpoint, available(bucket)))
+                .collect(),
+            paused_for: self
+                .inner
+                .paused_until
+                .lock()
+                .unwrap()
+                .filter(|until| *until > now)
+                .map(|until| until - now),
+        };
+    }
+
+    /// Number of requests currently delayed by the limiter.
+    pub fn queue_depth(&self) -> usize {
+        return self.inner.queued.load(Ordering::Relaxed);
+    }
+
+    /// Wait until a request to `endpoint` can be sent.
+    ///
+    /// Cancelling the returned future gives the reserved slot back.
+    pub(crate) async fn acquire(&self, endpoint: &str) {
+        let now = Instant::now();
+        let paused_until = *self.inner.paused_until.lock().unwrap();
+        let buckets = [
+            self.inner.global.as_ref(),
+            self.inner.endpoints.get(endpoint),
+        ];
+
+        // Reserve a slot in every bucket, so concurrent requests queue up
+        // behind each other instead of waking up at the same time.
+        let ready_at = buckets
+            .into_iter()
+            .flatten()
+            .map(|bucket| bucket.lock().unwrap().reserve(now))
+            .chain(paused_until)
+            .max();
+
+        let Some(ready_at) = ready_at.filter(|ready_at| *ready_at > now) else {
+            return;
+        };
+
+        self.inner.queued.fetch_add(1, Ordering::Relaxed);
+        let _queued = Dequeue(&self.inner.queued);
+        let mut reservation = Reservation(buckets);
+        tokio::time::sleep_until(ready_at).await;
+        reservation.keep();
+    }
+
+    /// Pause requests as requested by `Retry-After` of a 429 response.
+    ///
+    /// Delay is capped at 5 minutes. Returns whether request should be
+    /// retried after `attempt` retries.
+    pub(crate) fn throttled(&self, headers: &HeaderMap, attempt: u32) -> bool {
+        let delay = retry_after(headers)
+            .unwrap_or(DEFAULT_RETRY_AFTER)
+            .min(MAX_RETRY_AFTER);
+        let Some(until) = Instant::now().checked_add(delay) else {
+            return attempt < self.inner.max_retries;
+        };
+
+        let mut paused_until = self.inner.paused_until.lock().unwrap();
+        *paused_until = Some(paused_until.map_or(until, |paused| paused.max(until)));
+
+        return attempt < self.inner.max_retries;
+    }
+
+    fn configure(&mut self) -> &mut Inner {
+        return Arc::get_mut(&mut self.inner).expect("limiter is configured before it is shared");
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// Token bucket, tracked as the time it becomes full again.
+///
+/// A request reserves one token, which may push the time past now, in
+/// which case the request has to wait until the token is refilled.
+#[derive(Debug)]
+struct Bucket {
+    rate: Rate,
+    /// Time at which all tokens are available again.
+    full_at: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Rate) -> Self {
+        return Self {
+            rate,
+            full_at: Instant::now(),
+        };
+    }
+
+    fn burst(&self) -> Duration {
+        return self.rate.interval() * self.rate.requests;
+    }
+
+    /// Reserve a token, returning when it may be used.
+    fn reserve(&mut self, now: Instant) -> Instant {
+        let full_at = self.full_at.max(now) + self.rate.interval();
+        self.full_at = full_at;
+
+        return full_at
+            .checked_sub(self.burst())
+            .map_or(now, |ready_at| ready_at.max(now));
+    }
+
+    /// Give back a token reserved by a request that was not sent.
+    ///
+    /// Requests already waiting keep their time, the token goes to the
+    /// next request.
+    fn release(&mut self) {
+        if let Some(full_at) = self.full_at.checked_sub(self.rate.interval()) {
+            self.full_at = full_at;
+        }
+    }
+
+    fn available(&self, now: Instant) -> u32 {
+        let missing = self.full_at.saturating_duration_since(now);
+        let refilled = self.burst().saturating_sub(missing);
+
+        return (refilled.as_nanos() / self.rate.interval().as_nanos().max(1)) as u32;
+    }
+}
+
+struct Dequeue<'a>(&'a AtomicUsize);
+
+impl Drop for Dequeue<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Tokens reserved in buckets, released unless kept.
+struct Reservation<'a>([Option<&'a Mutex<Bucket>>; 2]);
+
+impl Reservation<'_> {
+    fn keep(&mut self) {
+        self.0 = [None, None];
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        for bucket in self.0.into_iter().flatten() {
+            bucket.lock().unwrap().release();
+        }
+    }
+}
+
+/// Delay from `Retry-After` header, either in seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    return Some(
+        (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    );
+}
@@ -0,0 +1,174 @@
+//! Reconciliation of local orders with payments known to MAIB.
+//!
+//! Payments are matched by `order_id`, so QRs have to be created with
+//! order id set for their payments to be reconciled.
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+use crate::{
+    api::MiaApi,
+    error::Result,
+    models::{
+        request::ListPayments, response::PaymentDetails, AccessToken, Currency, PaymentStatus,
+    },
+};
+
+/// Number of payments fetched per request.
+const PAGE_SIZE: u32 = 100;
+
+/// Status of a payment in local ledger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExpectedStatus {
+    Paid,
+    PartiallyRefunded,
+    Refunded,
+}
+
+impl ExpectedStatus {
+    fn matches(self, status: &PaymentStatus) -> bool {
+        return matches!(
+            (self, status),
+            (ExpectedStatus::Paid, PaymentStatus::Executed)
+                | (
+                    ExpectedStatus::PartiallyRefunded,
+                    PaymentStatus::PartiallyRefunded
+                )
+                | (ExpectedStatus::Refunded, PaymentStatus::Refunded)
+        );
+    }
+}
+
+/// Payment as recorded in local ledger.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectedPayment {
+    pub order_id: String,
+    pub amount: Decimal,
+    pub currency: Currency,
+    pub status: ExpectedStatus,
+}
+
+/// Local payment and MAIB payment with the same order id.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pair {
+    pub expected: ExpectedPayment,
+    pub actual: PaymentDetails,
+}
+
+/// Outcome of reconciliation.
+///
+/// Pair with both amount and status mismatched is listed in both
+/// `amount_mismatches` and `status_mismatches`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconciliationReport {
+    pub matched: Vec<Pair>,
+    /// Amount or currency differ.
+    pub amount_mismatches: Vec<Pair>,
+    pub status_mismatches: Vec<Pair>,
+    /// MAIB payments without a local payment.
+    pub missing_locally: Vec<PaymentDetails>,
+    /// Local payments without a MAIB payment.
+    pub missing_at_maib: Vec<ExpectedPayment>,
+}
+
+impl ReconciliationReport {
+    /// Whether every payment matched.
+    pub fn is_clean(&self) -> bool {
+        return self.amount_mismatches.is_empty()
+            && self.status_mismatches.is_empty()
+            && self.missing_locally.is_empty()
+            && self.missing_at_maib.is_empty();
+    }
+}
+
+/// Fetch payments executed in `[from, to)` and compare them with `expected`.
+pub async fn reconcile<A, I>(
+    api: &A,
+    token: &AccessToken,
+    expected: I,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<ReconciliationReport>
+where
+    A: MiaApi,
+    I: IntoIterator<Item = ExpectedPayment>,
+{
+    let mut payments = Vec::new();
+
+    loop {
+        let query = ListPayments {
+            count: Some(PAGE_SIZE),
+            offset: Some(payments.len() as u32),
+            executed_at_from: Some(from),
+            executed_at_to: Some(to),
+            ..Default::default()
+        };
+
+        let page = api.list_payments(&query, token).await?;
+        let fetched = page.items.len();
+        payments.extend(page.items);
+
+        if fetched == 0 || payments.len() as u64 >= page.total_count {
+            break;
+        }
+    }
+
+    return Ok(compare(expected, payments));
+}
+
+/// Compare local payments with MAIB payments, matching them by order id.
+///
+/// When several MAIB payments share an order id, they are paired with
+/// local payments in order, the rest is reported as missing locally.
+pub fn compare<I>(expected: I, payments: Vec<PaymentDetails>) -> ReconciliationReport
+where
+    I: IntoIterator<Item = ExpectedPayment>,
+{
+    let mut report = ReconciliationReport::default();
+    let mut payments: Vec<Option<PaymentDetails>> = payments.into_iter().map(Some).collect();
+
+    // Indices of payments per order id, in order.
+    let mut by_order_id: HashMap<String, VecDeque<usize>> = HashMap::new();
+    for (index, payment) in payments.iter().enumerate() {
+        if let Some(order_id) = payment.as_ref().and_then(|p| p.order_id.clone()) {
+            by_order_id.entry(order_id).or_default().push_back(index);
+        }
+    }
+
+    for expected in expected {
+        let actual = by_order_id
+            .get_mut(&expected.order_id)
+            .and_then(VecDeque::pop_front)
+            .and_then(|index| payments[index].take());
+
+        let Some(actual) = actual else {
+            report.missing_at_maib.push(expected);
+            continue;
+        };
+
+        let amount_matches =
+            expected.amount == actual.amount && expected.currency == actual.currency;
+        let status_matches = expected.status.matches(&actual.status);
+        let pair = Pair { expected, actual };
+
+        match (amount_matches, status_matches) {
+            (true, true) => report.matched.push(pair),
+            (false, true) => report.amount_mismatches.push(pair),
+            (true, false) => report.status_mismatches.push(pair),
+            (false, false) => {
+                report.amount_mismatches.push(pair.clone());
+                report.status_mismatches.push(pair);
+            }
+        }
+    }
+
+    report.missing_locally = payments.into_iter().flatten().collect();
+
+    return report;
+}
@@ -0,0 +1,312 @@
+//! Rendering of MIA QR codes into images.
+//!
+//! Available with `render` feature.
+
+use crate::{
+    error::{Error, Result},
+    models::response::{CreateQRResponse, GetQRDetails},
+};
+
+/// QR error correction level.
+///
+/// Higher levels survive more damage at the cost of a denser code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorCorrection {
+    /// Recovers ~7% of data.
+    Low,
+    /// Recovers ~15% of data.
+    #[default]
+    Medium,
+    /// Recovers ~25% of data.
+    Quartile,
+    /// Recovers ~30% of data.
+    High,
+}
+
+impl From<ErrorCorrection> for qrcode::EcLevel {
+    fn from(value: ErrorCorrection) -> Self {
+        match value {
+            ErrorCorrection::Low => qrcode::EcLevel::L,
+            ErrorCorrection::Medium => qrcode::EcLevel::M,
+            ErrorCorrection::Quartile => qrcode::EcLevel::Q,
+            ErrorCorrection::High => qrcode::EcLevel::H,
+        }
+    }
+}
+
+/// Frame drawn around the QR code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branding {
+    /// Frame color as RGB.
+    pub color: [u8; 3],
+
+    /// Frame thickness, in modules.
+    pub thickness: u32,
+
+    /// Caption rendered under the code.
+    ///
+    /// Only SVG output renders the caption, PNG and terminal
+    /// output draw the frame only.
+    pub caption: Option<String>,
+}
+
+impl Branding {
+    /// MIA branded frame.
+    pub fn mia() -> Self {
+        return Self {
+            color: [0x00, 0x6b, 0x5f],
+            thickness: 2,
+            caption: Some("MIA".to_owned()),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Minimal width of produced image, in pixels.
+    ///
+    /// Module size is picked so that image is at least this wide,
+    /// final image might be slightly larger. Ignored by terminal output.
+    pub size: u32,
+
+    /// Quiet zone around the code, in modules.
+    pub margin: u32,
+
+    pub error_correction: ErrorCorrection,
+
+    pub branding: Option<Branding>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        return Self {
+            size: 256,
+            margin: 4,
+            error_correction: ErrorCorrection::default(),
+            branding: None,
+        };
+    }
+}
+
+/// Render QR codes returned by MIA API.
+pub trait RenderQR {
+    /// Data encoded in the QR code.
+    fn qr_url(&self) -> &str;
+
+    /// Render QR code as SVG document.
+    fn to_svg(&self, options: &RenderOptions) -> Result<String> {
+        let matrix = Matrix::encode(self.qr_url(), options)?;
+        return Ok(matrix.to_svg(options));
+    }
+
+    /// Render QR code as PNG image bytes.
+    fn to_png(&self, options: &RenderOptions) -> Result<Vec<u8>> {
+        let matrix = Matrix::encode(self.qr_url(), options)?;
+        return matrix.to_png(options);
+    }
+
+    /// Render QR code as a string that can be printed in a terminal.
+    ///
+    /// Every character holds two rows of modules.
+    fn to_terminal(&self, options: &RenderOptions) -> Result<String> {
+        let matrix = Matrix::encode(self.qr_url(), options)?;
+        return Ok(matrix.to_terminal());
+    }
+}
+
+impl RenderQR for CreateQRResponse {
+    fn qr_url(&self) -> &str {
+        return &self.url;
+    }
+}
+
+impl RenderQR for GetQRDetails {
+    fn qr_url(&self) -> &str {
+        return &self.url;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Module {
+    Light,
+    Dark,
+    Frame,
+}
+
+/// QR modules with margin and frame applied.
+struct Matrix {
+    width: usize,
+    modules: Vec<Module>,
+}
+
+impl Matrix {
+    fn encode(data: &str, options: &RenderOptions) -> Result<Self> {
+        let code =
+            qrcode::QrCode::with_error_correction_level(data, options.error_correction.into())
+                .map_err(|err| Error::Render(format!("error encoding qr: {err}")))?;
+
+        let code_width = code.width();
+        let colors = code.to_colors();
+        let margin = options.margin as usize;
+        let frame = options
+            .branding
+            .as_ref()
+            .map(|b| b.thickness as usize)
+            .unwrap_or(0);
+        let offset = margin + frame;
+        let width = code_width + offset * 2;
+
+        let mut modules = vec![Module::Light; width * width];
+        for y in 0..width {
+            for x in 0..width {
+                let in_frame = frame > 0
+                    && (x < frame || y < frame || x >= width - frame || y >= width - frame);
+                let in_code = (offset..offset + code_width).contains(&x)
+                    && (offset..offset + code_width).contains(&y);
+
+                modules[y * width + x] = if in_frame {
+                    Module::Frame
+                } else if in_code
+                    && colors[(y - offset) * code_width + (x - offset)] == qrcode::Color::Dark
+                {
+                    Module::Dark
+                } else {
+                    Module::Light
+                };
+            }
+        }
+
+        return Ok(Self { width, modules });
+    }
+
+    fn get(&self, x: usize, y: usize) -> Module {
+        return self.modules[y * self.width + x];
+    }
+
+    fn module_size(&self, options: &RenderOptions) -> u32 {
+        let width = self.width as u32;
+        return options.size.div_ceil(width).max(1);
+    }
+
+    fn to_svg(&self, options: &RenderOptions) -> String {
+        let module = self.module_size(options);
+        let side = module * self.width as u32;
+        let branding = options.branding.as_ref();
+        let caption = branding.and_then(|b| b.caption.as_deref());
+        let caption_height = if caption.is_some() { module * 6 } else { 0 };
+        let frame_color = branding.map(|b| rgb_hex(b.color)).unwrap_or_default();
+
+        let mut svg = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" "#,
+                r#"width="{w}" height="{h}" viewBox="0 0 {w} {h}" shape-rendering="crispEdges">"#,
+                r##"<rect width="{w}" height="{h}" fill="#ffffff"/>"##,
+            ),
+            w = side,
+            h = side + caption_height,
+        );
+
+        let mut dark_path = String::new();
+        let mut frame_path = String::new();
+        for y in 0..self.width {
+            for x in 0..self.width {
+                let path = match self.get(x, y) {
+                    Module::Light => continue,
+                    Module::Dark => &mut dark_path,
+                    Module::Frame => &mut frame_path,
+                };
+                path.push_str(&format!(
+                    "M{},{}h{m}v{m}h-{m}z",
+                    x as u32 * module,
+                    y as u32 * module,
+                    m = module,
+                ));
+            }
+        }
+
+        svg.push_str(&format!(r##"<path fill="#000000" d="{dark_path}"/>"##));
+
+        if !frame_path.is_empty() {
+            svg.push_str(&format!(r#"<path fill="{frame_color}" d="{frame_path}"/>"#));
+        }
+
+        if let Some(caption) = caption {
+            svg.push_str(&format!(
+                r#"<rect y="{side}" width="{side}" height="{caption_height}" fill="{frame_color}"/>"#
+            ));
+            svg.push_str(&format!(
+                concat!(
+                    r##"<text x="{x}" y="{y}" fill="#ffffff" font-family="sans-serif" "##,
+                    r#"font-size="{size}" font-weight="bold" text-anchor="middle" "#,
+                    r#"dominant-baseline="central">{text}</text>"#,
+                ),
+                x = side / 2,
+                y = side + caption_height / 2,
+                size = module * 4,
+                text = escape_xml(caption),
+            ));
+        }
+
+        svg.push_str("</svg>");
+        return svg;
+    }
+
+    fn to_png(&self, options: &RenderOptions) -> Result<Vec<u8>> {
+        let module = self.module_size(options);
+        let side = module * self.width as u32;
+        let frame_color = options
+            .branding
+            .as_ref()
+            .map(|b| b.color)
+            .unwrap_or([0, 0, 0]);
+
+        let image = image::RgbImage::from_fn(side, side, |x, y| {
+            let color = match self.get((x / module) as usize, (y / module) as usize) {
+                Module::Light => [0xff, 0xff, 0xff],
+                Module::Dark => [0, 0, 0],
+                Module::Frame => frame_color,
+            };
+            image::Rgb(color)
+        });
+
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .map_err(|err| Error::Render(format!("error encoding png: {err}")))?;
+
+        return Ok(bytes.into_inner());
+    }
+
+    fn to_terminal(&self) -> String {
+        let is_dark = |x: usize, y: usize| y < self.width && self.get(x, y) != Module::Light;
+        let mut out = String::new();
+
+        for y in (0..self.width).step_by(2) {
+            for x in 0..self.width {
+                let c = match (is_dark(x, y), is_dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                };
+                out.push(c);
+            }
+            out.push('\n');
+        }
+
+        return out;
+    }
+}
+
+fn rgb_hex(color: [u8; 3]) -> String {
+    return format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2]);
+}
+
+fn escape_xml(value: &str) -> String {
+    return value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
@@ -0,0 +1,176 @@
+//! Diagnostics of API calls.
+//!
+//! Only endpoint templates, ids and status codes are reported,
+//! request and response bodies, tokens and payer details never are.
+//!
+//! With `metrics` feature following metrics are recorded:
+//! - `maib_requests_total` counter, labels: `endpoint`, `method`, `status`.
+//! - `maib_request_duration_seconds` histogram, labels: `endpoint`, `method`.
+//! - `maib_request_errors_total` counter, labels: `endpoint`, `kind`.
+//! - `maib_token_refreshes_total` counter, labels: `outcome`.
+//! - `maib_webhook_verifications_total` counter, labels: `outcome`.
+
+use std::time::Instant;
+
+use http::StatusCode;
+
+#[cfg(feature = "tracing")]
+use crate::error::Error;
+use crate::{endpoint::SendRequestInput, error::Result};
+
+#[cfg(feature = "metrics")]
+const TOKEN_ENDPOINT: &str = "/v2/auth/token";
+
+/// Observation of a single API call.
+pub(crate) struct Observation {
+    started_at: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "metrics")]
+    endpoint: &'static str,
+    #[cfg(feature = "metrics")]
+    method: http::Method,
+    /// Response status code, `0` until response is received.
+    #[cfg(feature = "metrics")]
+    status: std::sync::atomic::AtomicU16,
+}
+
+impl Observation {
+    pub(crate) fn start<B: serde::Serialize>(input: &SendRequestInput<'_, B>) -> Self {
+        #[cfg(feature = "tracing")]
+        let span = {
+            let span = tracing::info_span!(
+                "maib.request",
+                method = %input.method,
+                endpoint = input.endpoint,
+                qr_id = tracing::field::Empty,
+                pay_id = tracing::field::Empty,
+                status = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+                retries = 0u32,
+            );
+
+            if let Some(qr_id) = input.qr_id {
+                span.record("qr_id", qr_id.as_str());
+            }
+
+            if let Some(pay_id) = input.pay_id {
+                span.record("pay_id", pay_id.as_str());
+            }
+
+            span
+        };
+
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        let _ = input;
+
+        return Self {
+            started_at: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span,
+            #[cfg(feature = "metrics")]
+            endpoint: input.endpoint,
+            #[cfg(feature = "metrics")]
+            method: input.method.clone(),
+            #[cfg(feature = "metrics")]
+            status: std::sync::atomic::AtomicU16::new(0),
+        };
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self) -> &tracing::Span {
+        return &self.span;
+    }
+
+    pub(crate) fn status(&self, status: StatusCode) {
+        #[cfg(feature = "tracing")]
+        self.span.record("status", status.as_u16());
+
+        #[cfg(feature = "metrics")]
+        self.status
+            .store(status.as_u16(), std::sync::atomic::Ordering::Relaxed);
+
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        let _ = status;
+    }
+
+    /// Record how many times the request was retried, e.g. after 429.
+    pub(crate) fn retries(&self, retries: u32) {
+        #[cfg(feature = "tracing")]
+        self.span.record("retries", retries);
+
+        #[cfg(not(feature = "tracing"))]
+        let _ = retries;
+    }
+
+    pub(crate) fn finish<R>(self, result: &Result<R>) {
+        let latency = self.started_at.elapsed();
+
+        #[cfg(feature = "tracing")]
+        {
+            let _entered = self.span.enter();
+            self.span.record("latency_ms", latency.as_millis() as u64);
+
+            match result {
+                Ok(_) => tracing::debug!("MAIB request succeeded"),
+                Err(Error::Api(errors)) => {
+                    let codes: Vec<&str> = errors.iter().map(|e| e.code()).collect();
+                    tracing::warn!(error.kind = "api", error.codes = ?codes, "MAIB API returned errors");
+                }
+                Err(err) => tracing::warn!(error.kind = err.kind(), "MAIB request failed"),
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            let status = match self.status.load(std::sync::atomic::Ordering::Relaxed) {
+                0 => "none".to_owned(),
+                status => status.to_string(),
+            };
+
+            metrics::counter!(
+                "maib_requests_total",
+                "endpoint" => self.endpoint,
+                "method" => self.method.to_string(),
+                "status" => status,
+            )
+            .increment(1);
+
+            metrics::histogram!(
+                "maib_request_duration_seconds",
+                "endpoint" => self.endpoint,
+                "method" => self.method.to_string(),
+            )
+            .record(latency.as_secs_f64());
+
+            if let Err(err) = result {
+                metrics::counter!(
+                    "maib_request_errors_total",
+                    "endpoint" => self.endpoint,
+                    "kind" => err.kind(),
+                )
+                .increment(1);
+            }
+
+            if self.endpoint == TOKEN_ENDPOINT {
+                let outcome = if result.is_ok() { "success" } else { "failure" };
+                metrics::counter!("maib_token_refreshes_total", "outcome" => outcome).increment(1);
+            }
+        }
+
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        let _ = (latency, result);
+    }
+}
+
+/// Record outcome of notification signature verification.
+pub(crate) fn webhook_verified(valid: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let outcome = if valid { "valid" } else { "invalid" };
+        metrics::counter!("maib_webhook_verifications_total", "outcome" => outcome).increment(1);
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    let _ = valid;
+}
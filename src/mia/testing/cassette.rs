@@ -0,0 +1,281 @@
+//! Record and replay of HTTP interactions.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde_json::json;
+
+use super::http::{Request, Response, Server};
+
+/// Value stored instead of tokens and secrets.
+pub const SCRUBBED: &str = "[scrubbed]";
+
+/// Body fields never written to a cassette.
+const SCRUBBED_FIELDS: &[&str] = &[
+    "accessToken",
+    "refreshToken",
+    "clientId",
+    "clientSecret",
+    "signature",
+];
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub body: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Recorded interactions, stored as a JSON file.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        return serde_json::from_slice(&bytes).map_err(std::io::Error::other);
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let bytes = serde_json::to_vec_pretty(self).map_err(std::io::Error::other)?;
+        return std::fs::write(path, bytes);
+    }
+}
+
+enum Mode {
+    Record { upstream: String, path: PathBuf },
+    Replay,
+}
+
+struct State {
+    mode: Mode,
+    cassette: Cassette,
+    used: Vec<bool>,
+    unmatched: Vec<RecordedRequest>,
+}
+
+/// Local server recording or replaying MAIB API interactions.
+///
+/// Pass [CassetteServer::base_url] to a client instead of MAIB url.
+/// Headers are never recorded, tokens and secrets in bodies are
+/// replaced with [SCRUBBED].
+///
+/// Requests are matched on method, path and body, every recorded
+/// interaction is replayed once, in recorded order. A request without a
+/// match gets `500` response and is reported by [CassetteServer::finish].
+pub struct CassetteServer {
+    server: Server,
+    state: Arc<Mutex<State>>,
+}
+
+impl CassetteServer {
+    /// Forward requests to `upstream_base_url` and record them to `path`.
+    ///
+    /// Cassette is written by [CassetteServer::finish] or when dropped.
+    pub fn record(path: impl Into<PathBuf>, upstream_base_url: String) -> std::io::Result<Self> {
+        let mode = Mode::Record {
+            upstream: upstream_base_url,
+            path: path.into(),
+        };
+
+        return Self::start(mode, Cassette::default());
+    }
+
+    /// Serve interactions recorded in `path`.
+    pub fn replay(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        return Self::start(Mode::Replay, Cassette::load(path)?);
+    }
+
+    fn start(mode: Mode, cassette: Cassette) -> std::io::Result<Self> {
+        let state = Arc::new(Mutex::new(State {
+            mode,
+            used: vec![false; cassette.interactions.len()],
+            cassette,
+            unmatched: Vec::new(),
+        }));
+
+        let handler_state = state.clone();
+        let server = Server::start(move |req| handle(&handler_state, req))?;
+
+        return Ok(Self { server, state });
+    }
+
+    pub fn base_url(&self) -> String {
+        return self.server.base_url();
+    }
+
+    /// Interactions recorded or loaded so far.
+    pub fn cassette(&self) -> Cassette {
+        return self.state.lock().unwrap().cassette.clone();
+    }
+
+    /// Write recorded cassette and fail if any request was not matched.
+    pub fn finish(self) -> std::io::Result<()> {
+        let state = self.state.lock().unwrap();
+
+        if let Mode::Record { ref path, .. } = state.mode {
+            state.cassette.save(path)?;
+        }
+
+        if !state.unmatched.is_empty() {
+            let requests: Vec<String> = state
+                .unmatched
+                .iter()
+                .map(|req| format!("{} {}", req.method, req.path))
+                .collect();
+
+            return Err(std::io::Error::other(format!(
+                "unmatched requests: {}",
+                requests.join(", ")
+            )));
+        }
+
+        return Ok(());
+    }
+}
+
+impl Drop for CassetteServer {
+    fn drop(&mut self) {
+        let Ok(state) = self.state.lock() else {
+            return;
+        };
+
+        if let Mode::Record { ref path, .. } = state.mode {
+            let _ = state.cassette.save(path);
+        }
+    }
+}
+
+fn handle(state: &Mutex<State>, req: Request) -> Response {
+    let mut state = state.lock().unwrap();
+    let recorded = RecordedRequest {
+        method: req.method.clone(),
+        path: req.path.clone(),
+        body: scrubbed_body(&req.body),
+    };
+
+    if let Mode::Record { ref upstream, .. } = state.mode {
+        let response = match forward(upstream, &req) {
+            Ok(response) => response,
+            Err(err) => {
+                state.unmatched.push(recorded);
+                return error(502, &format!("error forwarding request: {err}"));
+            }
+        };
+
+        state.cassette.interactions.push(Interaction {
+            request: recorded,
+            response: RecordedResponse {
+                status: response.status,
+                body: scrubbed_body(&response.body),
+            },
+        });
+        state.used.push(true);
+
+        return response;
+    }
+
+    let position = state
+        .cassette
+        .interactions
+        .iter()
+        .zip(state.used.iter())
+        .position(|(interaction, used)| !used && interaction.request == recorded);
+
+    let Some(position) = position else {
+        let message = format!("no recorded interaction for {} {}", req.method, req.path);
+        state.unmatched.push(recorded);
+        return error(500, &message);
+    };
+
+    state.used[position] = true;
+    let recorded = &state.cassette.interactions[position].response;
+
+    return Response {
+        status: recorded.status,
+        headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+        body: recorded
+            .body
+            .as_ref()
+            .map(|body| serde_json::to_vec(body).unwrap())
+            .unwrap_or_default(),
+    };
+}
+
+fn forward(upstream: &str, req: &Request) -> reqwest::Result<Response> {
+    let method = reqwest::Method::from_bytes(req.method.as_bytes()).unwrap_or_default();
+    let mut builder =
+        reqwest::blocking::Client::new().request(method, format!("{upstream}{}", req.path));
+
+    for name in ["accept", "authorization", "content-type"] {
+        if let Some(value) = req.header(name) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    let res = builder.body(req.body.clone()).send()?;
+    let status = res.status().as_u16();
+    let body = res.bytes()?.to_vec();
+
+    return Ok(Response {
+        status,
+        headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+        body,
+    });
+}
+
+fn error(status: u16, message: &str) -> Response {
+    return Response::json(
+        status,
+        &json!({
+            "errors": [{ "errorCode": "cassette", "errorMessage": message }],
+            "ok": false,
+        }),
+    );
+}
+
+fn scrubbed_body(body: &[u8]) -> Option<serde_json::Value> {
+    if body.is_empty() {
+        return None;
+    }
+
+    let mut value = match serde_json::from_slice(body) {
+        Ok(value) => value,
+        Err(_) => serde_json::Value::String(String::from_utf8_lossy(body).into_owned()),
+    };
+    scrub(&mut value);
+
+    return Some(value);
+}
+
+fn scrub(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SCRUBBED_FIELDS.contains(&key.as_str()) {
+                    *value = serde_json::Value::String(SCRUBBED.to_owned());
+                } else {
+                    scrub(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(scrub),
+        _ => {}
+    }
+}
@@ -0,0 +1,182 @@
+//! Minimal HTTP/1.1 server used by test servers.
+//!
+//! Every connection serves a single request and is closed afterwards.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Request {
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        return self
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Response {
+    pub(crate) fn json(status: u16, body: &serde_json::Value) -> Self {
+        return Self {
+            status,
+            headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+            body: serde_json::to_vec(body).unwrap(),
+        };
+    }
+}
+
+/// Running HTTP server, stopped when dropped.
+pub(crate) struct Server {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Server {
+    pub(crate) fn start<H>(handler: H) -> std::io::Result<Self>
+    where
+        H: Fn(Request) -> Response + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let handler = Arc::new(handler);
+
+        let stop = stopped.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let Ok(stream) = stream else {
+                    continue;
+                };
+
+                let handler = handler.clone();
+                thread::spawn(move || serve(stream, handler.as_ref()));
+            }
+        });
+
+        return Ok(Self { addr, stopped });
+    }
+
+    pub(crate) fn base_url(&self) -> String {
+        return format!("http://{}", self.addr);
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so it can see the flag.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn serve<H>(stream: TcpStream, handler: &H)
+where
+    H: Fn(Request) -> Response,
+{
+    let mut reader = BufReader::new(&stream);
+    let Some(request) = read_request(&mut reader) else {
+        return;
+    };
+
+    let response = handler(request);
+    let _ = write_response(&stream, &response);
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> Option<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let path = parts.next()?.to_owned();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        let (key, value) = line.split_once(':')?;
+        headers.push((key.trim().to_owned(), value.trim().to_owned()));
+    }
+
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+
+    let length: usize = request
+        .header("content-length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).ok()?;
+
+    return Some(request);
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reason(response.status)
+    );
+
+    for (key, value) in &response.headers {
+        head.push_str(&format!("{key}: {value}\r\n"));
+    }
+
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body)?;
+    return stream.flush();
+}
+
+fn reason(status: u16) -> &'static str {
+    return match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+}
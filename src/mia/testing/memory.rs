@@ -0,0 +1,67 @@
+//! Transport answering requests in-process.
+
+use std::sync::{Arc, Mutex};
+
+use crate::transport::{HttpRequest, HttpResponse, HttpTransport, SendFuture};
+
+type Handler = dyn Fn(&HttpRequest) -> HttpResponse + Send + Sync;
+
+/// [HttpTransport] answering requests with a handler, without opening
+/// sockets.
+///
+/// Handled requests are kept and can be inspected with
+/// [InMemoryTransport::take_requests]. Clones share the handler and
+/// handled requests.
+#[derive(Clone)]
+pub struct InMemoryTransport {
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<HttpRequest>>>,
+}
+
+impl core::fmt::Debug for InMemoryTransport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return f
+            .debug_struct("InMemoryTransport")
+            .field("requests", &self.requests)
+            .finish_non_exhaustive();
+    }
+}
+
+impl InMemoryTransport {
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    {
+        return Self {
+            handler: Arc::new(handler),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        };
+    }
+
+    /// Transport answering every request with `status` and JSON `body`.
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        let body = serde_json::to_vec(&body).unwrap();
+
+        return Self::new(move |_| {
+            return http::Response::builder()
+                .status(status)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(body.clone())
+                .unwrap();
+        });
+    }
+
+    /// Requests handled since the last call, in order.
+    pub fn take_requests(&self) -> Vec<HttpRequest> {
+        return std::mem::take(&mut *self.requests.lock().unwrap());
+    }
+}
+
+impl HttpTransport for InMemoryTransport {
+    fn send(&self, req: HttpRequest) -> SendFuture<'_> {
+        let res = (self.handler)(&req);
+        self.requests.lock().unwrap().push(req);
+
+        return Box::pin(core::future::ready(Ok(res)));
+    }
+}
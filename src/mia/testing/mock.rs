@@ -0,0 +1,256 @@
+//! In-memory [MiaApi] implementation.
+
+use core::future::Future;
+use std::{collections::VecDeque, sync::Mutex};
+
+use crate::{
+    api::MiaApi,
+    error::Result,
+    models::{
+        request::{self, CancelQR, ListPayments, ListQRs, OwnedCreateQR, RefundPayment},
+        response::{self, AuthToken},
+        AccessToken, ClientId, ClientSecret, PaymentId, QRId,
+    },
+};
+
+/// Call received by [MockMiaApi].
+///
+/// Tokens and client secrets are not recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCall {
+    GetAccessToken {
+        client_id: ClientId,
+    },
+    CreateQR {
+        payload: OwnedCreateQR,
+    },
+    GetQR {
+        qr_id: QRId,
+    },
+    CancelQR {
+        qr_id: QRId,
+        payload: CancelQR,
+    },
+    GetPayment {
+        pay_id: PaymentId,
+    },
+    RefundPayment {
+        pay_id: PaymentId,
+        payload: RefundPayment,
+    },
+    ListQRs {
+        query: ListQRs,
+    },
+    ListPayments {
+        query: ListPayments,
+    },
+}
+
+/// Mock of MIA API with scripted responses.
+///
+/// Responses are queued per operation with `push_*` methods and returned
+/// in order. A call without a queued response panics.
+///
+/// ```
+/// use maib_client::{api::MiaApi, models::{AccessToken, QRId}, testing::MockMiaApi};
+/// # async fn example() {
+/// let mock = MockMiaApi::new();
+/// mock.push_get_qr(Err(maib_client::error::Error::Unauthorized));
+///
+/// let token = AccessToken::new("token".to_owned());
+/// let result = mock.get_qr(&QRId::new("qr".to_owned()), &token).await;
+///
+/// assert!(result.is_err());
+/// assert_eq!(mock.calls().len(), 1);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct MockMiaApi {
+    state: Mutex<MockState>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    calls: Vec<MockCall>,
+    get_access_token: VecDeque<Result<AuthToken>>,
+    create_qr: VecDeque<Result<response::CreateQRResponse>>,
+    get_qr: VecDeque<Result<response::GetQRDetails>>,
+    cancel_qr: VecDeque<Result<response::CancelQR>>,
+    get_payment: VecDeque<Result<response::PaymentDetails>>,
+    refund_payment: VecDeque<Result<response::RefundPayment>>,
+    list_qrs: VecDeque<Result<response::Page<response::GetQRDetails>>>,
+    list_payments: VecDeque<Result<response::Page<response::PaymentDetails>>>,
+}
+
+impl MockMiaApi {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Calls received so far, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        return self.state.lock().unwrap().calls.clone();
+    }
+
+    pub fn push_get_access_token(&self, result: Result<AuthToken>) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .get_access_token
+            .push_back(result);
+        return self;
+    }
+
+    pub fn push_create_qr(&self, result: Result<response::CreateQRResponse>) -> &Self {
+        self.state.lock().unwrap().create_qr.push_back(result);
+        return self;
+    }
+
+    pub fn push_get_qr(&self, result: Result<response::GetQRDetails>) -> &Self {
+        self.state.lock().unwrap().get_qr.push_back(result);
+        return self;
+    }
+
+    pub fn push_cancel_qr(&self, result: Result<response::CancelQR>) -> &Self {
+        self.state.lock().unwrap().cancel_qr.push_back(result);
+        return self;
+    }
+
+    pub fn push_get_payment(&self, result: Result<response::PaymentDetails>) -> &Self {
+        self.state.lock().unwrap().get_payment.push_back(result);
+        return self;
+    }
+
+    pub fn push_refund_payment(&self, result: Result<response::RefundPayment>) -> &Self {
+        self.state.lock().unwrap().refund_payment.push_back(result);
+        return self;
+    }
+
+    pub fn push_list_qrs(&self, result: Result<response::Page<response::GetQRDetails>>) -> &Self {
+        self.state.lock().unwrap().list_qrs.push_back(result);
+        return self;
+    }
+
+    pub fn push_list_payments(
+        &self,
+        result: Result<response::Page<response::PaymentDetails>>,
+    ) -> &Self {
+        self.state.lock().unwrap().list_payments.push_back(result);
+        return self;
+    }
+
+    fn respond<R>(
+        &self,
+        call: MockCall,
+        queue: impl FnOnce(&mut MockState) -> &mut VecDeque<Result<R>>,
+    ) -> Result<R> {
+        let mut state = self.state.lock().unwrap();
+        let name = format!("{call:?}");
+        state.calls.push(call);
+
+        return match queue(&mut state).pop_front() {
+            Some(result) => result,
+            None => panic!("MockMiaApi: no response queued for {name}"),
+        };
+    }
+}
+
+impl MiaApi for MockMiaApi {
+    fn get_access_token(
+        &self,
+        id: &ClientId,
+        _secret: &ClientSecret,
+    ) -> impl Future<Output = Result<AuthToken>> + Send {
+        let call = MockCall::GetAccessToken {
+            client_id: id.clone(),
+        };
+        let result = self.respond(call, |state| &mut state.get_access_token);
+        return core::future::ready(result);
+    }
+
+    fn create_qr(
+        &self,
+        payload: &request::CreateQR<'_>,
+        _token: &AccessToken,
+    ) -> impl Future<Output = Result<response::CreateQRResponse>> + Send {
+        let call = MockCall::CreateQR {
+            payload: payload.to_owned_request(),
+        };
+        let result = self.respond(call, |state| &mut state.create_qr);
+        return core::future::ready(result);
+    }
+
+    fn get_qr(
+        &self,
+        qr_id: &QRId,
+        _token: &AccessToken,
+    ) -> impl Future<Output = Result<response::GetQRDetails>> + Send {
+        let call = MockCall::GetQR {
+            qr_id: qr_id.clone(),
+        };
+        let result = self.respond(call, |state| &mut state.get_qr);
+        return core::future::ready(result);
+    }
+
+    fn cancel_qr(
+        &self,
+        qr_id: &QRId,
+        payload: &CancelQR,
+        _token: &AccessToken,
+    ) -> impl Future<Output = Result<response::CancelQR>> + Send {
+        let call = MockCall::CancelQR {
+            qr_id: qr_id.clone(),
+            payload: payload.clone(),
+        };
+        let result = self.respond(call, |state| &mut state.cancel_qr);
+        return core::future::ready(result);
+    }
+
+    fn get_payment(
+        &self,
+        id: &PaymentId,
+        _token: &AccessToken,
+    ) -> impl Future<Output = Result<response::PaymentDetails>> + Send {
+        let call = MockCall::GetPayment { pay_id: id.clone() };
+        let result = self.respond(call, |state| &mut state.get_payment);
+        return core::future::ready(result);
+    }
+
+    fn refund_payment(
+        &self,
+        id: &PaymentId,
+        payload: &RefundPayment,
+        _token: &AccessToken,
+    ) -> impl Future<Output = Result<response::RefundPayment>> + Send {
+        let call = MockCall::RefundPayment {
+            pay_id: id.clone(),
+            payload: payload.clone(),
+        };
+        let result = self.respond(call, |state| &mut state.refund_payment);
+        return core::future::ready(result);
+    }
+
+    fn list_qrs(
+        &self,
+        query: &ListQRs,
+        _token: &AccessToken,
+    ) -> impl Future<Output = Result<response::Page<response::GetQRDetails>>> + Send {
+        let call = MockCall::ListQRs {
+            query: query.clone(),
+        };
+        let result = self.respond(call, |state| &mut state.list_qrs);
+        return core::future::ready(result);
+    }
+
+    fn list_payments(
+        &self,
+        query: &ListPayments,
+        _token: &AccessToken,
+    ) -> impl Future<Output = Result<response::Page<response::PaymentDetails>>> + Send {
+        let call = MockCall::ListPayments {
+            query: query.clone(),
+        };
+        let result = self.respond(call, |state| &mut state.list_payments);
+        return core::future::ready(result);
+    }
+}
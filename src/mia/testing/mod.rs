@@ -0,0 +1,16 @@
+//! Test support utilities.
+//!
+//! Available with `testing` feature.
+
+mod cassette;
+mod http;
+mod memory;
+mod mock;
+mod server;
+
+pub use cassette::{
+    Cassette, CassetteServer, Interaction, RecordedRequest, RecordedResponse, SCRUBBED,
+};
+pub use memory::InMemoryTransport;
+pub use mock::{MockCall, MockMiaApi};
+pub use server::{FakeServer, FakeServerConfig};
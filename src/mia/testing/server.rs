@@ -0,0 +1,682 @@
+//! In-process fake MAIB API server.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rust_decimal::Decimal;
+use serde_json::json;
+
+use super::http::{Request, Response, Server};
+use crate::models::{
+    request::{CancelQR, ListPayments, ListQRs, OwnedCreateQR, OwnedGetAccessToken, RefundPayment},
+    response::{GetQRDetails, PaymentDetails},
+    AccessToken, ClientId, ClientSecret, Currency, ExtensionId, Notification, NotificationPayload,
+    PaymentId, PaymentStatus, PaymentType, QRId, QRStatus, QRType, RefundNotification,
+    RefundNotificationPayload, Signature, SignatureKey,
+};
+
+/// Page size of lists when `count` is not set.
+const DEFAULT_PAGE_SIZE: u32 = 10;
+
+#[derive(Debug, Clone)]
+pub struct FakeServerConfig {
+    /// Credentials accepted by `/v2/auth/token`.
+    pub client_id: ClientId,
+    pub client_secret: ClientSecret,
+
+    /// Key used to sign notifications and refund callbacks.
+    pub signature_key: SignatureKey,
+
+    /// Lifetime of issued access tokens.
+    pub token_lifetime: Duration,
+}
+
+impl Default for FakeServerConfig {
+    fn default() -> Self {
+        return Self {
+            client_id: ClientId::new("fake-client-id".to_owned()),
+            client_secret: ClientSecret::new("fake-client-secret".to_owned()),
+            signature_key: SignatureKey::from("fake-signature-key".to_owned()),
+            token_lifetime: Duration::from_secs(300),
+        };
+    }
+}
+
+/// Fake MAIB API listening on a local port.
+///
+/// Implements authentication, QR creation, details, cancellation and list,
+/// `/v2/mia/test-pay`, payment details, list and full or partial refunds.
+/// Paying a QR sends a signed notification to its `callback_url`, signed
+/// refund status is sent to refund `callbackUrl`, if one is set.
+///
+/// Server is stopped when dropped.
+pub struct FakeServer {
+    server: Server,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeServer {
+    /// Start server with default configuration.
+    pub fn start() -> Self {
+        return Self::with_config(FakeServerConfig::default());
+    }
+
+    pub fn with_config(config: FakeServerConfig) -> Self {
+        let state = Arc::new(Mutex::new(State::new(config)));
+
+        let handler_state = state.clone();
+        let server = Server::start(move |req| handle(&handler_state, req))
+            .expect("failed to start fake MAIB server");
+
+        return Self { server, state };
+    }
+
+    /// Base url to be passed to a client.
+    pub fn base_url(&self) -> String {
+        return self.server.base_url();
+    }
+
+    pub fn config(&self) -> FakeServerConfig {
+        return self.state.lock().unwrap().config.clone();
+    }
+
+    /// Issue a valid access token without calling the API.
+    pub fn issue_token(&self) -> AccessToken {
+        return self.state.lock().unwrap().issue_token();
+    }
+
+    /// Current state of a QR.
+    pub fn qr(&self, id: &QRId) -> Option<GetQRDetails> {
+        let mut state = self.state.lock().unwrap();
+        return state.qr(id.as_str()).cloned();
+    }
+
+    /// Current state of a payment.
+    pub fn payment(&self, id: &PaymentId) -> Option<PaymentDetails> {
+        let state = self.state.lock().unwrap();
+        return state.payments.get(id.as_str()).cloned();
+    }
+
+    /// Answer next `count` requests with 429 and `Retry-After` set to
+    /// `retry_after` seconds.
+    pub fn throttle(&self, count: u32, retry_after: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.throttled = Some((count, retry_after));
+    }
+
+    /// Signed notifications produced so far, in order.
+    ///
+    /// Notifications are listed even if delivery to `callback_url` failed.
+    pub fn notifications(&self) -> Vec<NotificationPayload> {
+        return self.state.lock().unwrap().notifications.clone();
+    }
+}
+
+struct State {
+    config: FakeServerConfig,
+    tokens: HashMap<String, Instant>,
+    qrs: HashMap<String, GetQRDetails>,
+    payments: HashMap<String, PaymentDetails>,
+    notifications: Vec<NotificationPayload>,
+    next_id: u64,
+    /// Requests left to answer with 429 and their `Retry-After`.
+    throttled: Option<(u32, u64)>,
+}
+
+impl State {
+    fn new(config: FakeServerConfig) -> Self {
+        return Self {
+            config,
+            tokens: HashMap::new(),
+            qrs: HashMap::new(),
+            payments: HashMap::new(),
+            notifications: Vec::new(),
+            next_id: 1,
+            throttled: None,
+        };
+    }
+
+    fn next_id(&mut self) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        return format!("{:08x}-0000-4000-8000-{:012x}", std::process::id(), id);
+    }
+
+    fn issue_token(&mut self) -> AccessToken {
+        let token = format!("fake-token-{}", self.next_id());
+        let expires_at = Instant::now() + self.config.token_lifetime;
+        self.tokens.insert(token.clone(), expires_at);
+
+        return AccessToken::new(token);
+    }
+
+    fn is_authorized(&self, req: &Request) -> bool {
+        let Some(token) = req
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+
+        return self
+            .tokens
+            .get(token)
+            .is_some_and(|expires_at| *expires_at > Instant::now());
+    }
+
+    /// Get QR, expiring it if needed.
+    fn qr(&mut self, id: &str) -> Option<&mut GetQRDetails> {
+        let qr = self.qrs.get_mut(id)?;
+
+        if qr.status == QRStatus::Active && qr.expires_at <= Utc::now() {
+            qr.status = QRStatus::Expired;
+            qr.updated_at = qr.expires_at;
+        }
+
+        return Some(qr);
+    }
+}
+
+fn handle(state: &Mutex<State>, req: Request) -> Response {
+    let mut state = state.lock().unwrap();
+    let (path, query) = match req.path.split_once('?') {
+        Some((path, query)) => (path.to_owned(), query.to_owned()),
+        None => (req.path.clone(), String::new()),
+    };
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    if let Some((count, retry_after)) = state.throttled {
+        state.throttled = (count > 1).then_some((count - 1, retry_after));
+
+        let mut response = errors(429, "tooManyRequests", "too many requests");
+        response
+            .headers
+            .push(("Retry-After".to_owned(), retry_after.to_string()));
+        return response;
+    }
+
+    if segments.as_slice() == ["v2", "auth", "token"] {
+        if req.method != "POST" {
+            return method_not_allowed();
+        }
+
+        return auth_token(&mut state, &req);
+    }
+
+    if !state.is_authorized(&req) {
+        return Response {
+            status: 401,
+            headers: Vec::new(),
+            body: Vec::new(),
+        };
+    }
+
+    return match (req.method.as_str(), segments.as_slice()) {
+        ("GET", ["v2", "mia", "qr"]) => list_qrs(&mut state, &query),
+        ("POST", ["v2", "mia", "qr"]) => create_qr(&mut state, &req),
+        ("GET", ["v2", "mia", "qr", id]) => get_qr(&mut state, id),
+        ("POST", ["v2", "mia", "qr", id, "cancel"]) => cancel_qr(&mut state, id, &req),
+        ("POST", ["v2", "mia", "test-pay"]) => test_pay(&mut state, &req),
+        ("GET", ["v2", "mia", "payments"]) => list_payments(&state, &query),
+        ("GET", ["v2", "mia", "payments", id]) => get_payment(&state, id),
+        ("POST", ["v2", "mia", "payments", id, "refund"]) => refund_payment(&mut state, id, &req),
+        _ => errors(404, "notFound", "resource not found"),
+    };
+}
+
+fn ok(result: serde_json::Value) -> Response {
+    return Response::json(200, &json!({ "result": result, "ok": true }));
+}
+
+fn errors(status: u16, code: &str, message: &str) -> Response {
+    return Response::json(
+        status,
+        &json!({
+            "errors": [{ "errorCode": code, "errorMessage": message }],
+            "ok": false,
+        }),
+    );
+}
+
+fn method_not_allowed() -> Response {
+    return errors(405, "methodNotAllowed", "method not allowed");
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(req: &Request) -> Result<T, Response> {
+    return serde_json::from_slice(&req.body).map_err(|err| {
+        errors(
+            400,
+            "invalidRequest",
+            &format!("invalid request body: {err}"),
+        )
+    });
+}
+
+fn parse_query<T: serde::de::DeserializeOwned>(query: &str) -> Result<T, Response> {
+    return serde_urlencoded::from_str(query).map_err(|err| {
+        errors(
+            400,
+            "invalidRequest",
+            &format!("invalid query parameters: {err}"),
+        )
+    });
+}
+
+/// Page of items ordered by id, i.e. by creation.
+fn page<T: serde::Serialize>(
+    mut items: Vec<(&String, T)>,
+    offset: Option<u32>,
+    count: Option<u32>,
+) -> Response {
+    items.sort_by_key(|(id, _)| *id);
+    let total_count = items.len();
+    let items: Vec<T> = items
+        .into_iter()
+        .map(|(_, item)| item)
+        .skip(offset.unwrap_or(0) as usize)
+        .take(count.unwrap_or(DEFAULT_PAGE_SIZE) as usize)
+        .collect();
+
+    return ok(json!({ "items": items, "totalCount": total_count }));
+}
+
+fn now_local() -> String {
+    return Utc::now()
+        .with_timezone(&chrono_tz::Europe::Chisinau)
+        .to_rfc3339_opts(SecondsFormat::Secs, false);
+}
+
+fn auth_token(state: &mut State, req: &Request) -> Response {
+    let body: OwnedGetAccessToken = match parse_body(req) {
+        Ok(body) => body,
+        Err(res) => return res,
+    };
+
+    if body.client_id != state.config.client_id || body.client_secret != state.config.client_secret
+    {
+        return errors(400, "invalidCredentials", "invalid client id or secret");
+    }
+
+    let token = state.issue_token();
+
+    return ok(json!({
+        "accessToken": token.as_str(),
+        "expiresIn": state.config.token_lifetime.as_secs(),
+        "tokenType": "Bearer",
+    }));
+}
+
+fn create_qr(state: &mut State, req: &Request) -> Response {
+    let body: OwnedCreateQR = match parse_body(req) {
+        Ok(body) => body,
+        Err(res) => return res,
+    };
+
+    if body.description.is_empty() {
+        return errors(400, "invalidDescription", "description is required");
+    }
+
+    match body.amount_type {
+        PaymentType::Fixed if body.amount <= Decimal::ZERO => {
+            return errors(400, "invalidAmount", "amount must be positive");
+        }
+        PaymentType::Controlled if body.amount_min.is_none() || body.amount_max.is_none() => {
+            return errors(400, "invalidAmount", "amountMin and amountMax are required");
+        }
+        PaymentType::Unknown(_) => {
+            return errors(400, "invalidAmountType", "unknown amount type");
+        }
+        _ => {}
+    }
+
+    let now = Utc::now();
+    let expires_at = match (&body.r#type, &body.expires_at) {
+        (_, Some(expires_at)) => match DateTime::parse_from_rfc3339(expires_at) {
+            Ok(expires_at) if expires_at > now => expires_at.with_timezone(&Utc),
+            Ok(_) => return errors(400, "invalidExpiresAt", "expiresAt must be in future"),
+            Err(_) => return errors(400, "invalidExpiresAt", "expiresAt is not a valid date"),
+        },
+        (QRType::Dynamic, None) => {
+            return errors(
+                400,
+                "invalidExpiresAt",
+                "expiresAt is required for Dynamic QR",
+            );
+        }
+        (_, None) => now + chrono::Duration::days(365),
+    };
+
+    let id = state.next_id();
+    let qr = GetQRDetails {
+        qr_id: QRId::new(id.clone()),
+        order_id: body.order_id,
+        status: QRStatus::Active,
+        r#type: body.r#type,
+        url: format!("https://fake.maib.md/qr/{id}"),
+        amount_type: body.amount_type,
+        currency: body.currency,
+        amount: body.amount,
+        amount_min: body.amount_min,
+        amount_max: body.amount_max,
+        description: body.description,
+        callback_url: body.callback_url,
+        redirect_url: body.redirect_url,
+        terminal_id: body.terminal_id.unwrap_or_else(|| "FAKE0001".to_owned()),
+        created_at: now,
+        updated_at: now,
+        expires_at,
+    };
+
+    let response = json!({
+        "qrId": qr.qr_id,
+        "orderId": qr.order_id,
+        "type": qr.r#type,
+        "url": qr.url,
+        "expiresAt": qr
+            .expires_at
+            .with_timezone(&chrono_tz::Europe::Chisinau)
+            .to_rfc3339_opts(SecondsFormat::Secs, false),
+    });
+
+    state.qrs.insert(id, qr);
+
+    return ok(response);
+}
+
+fn list_qrs(state: &mut State, query: &str) -> Response {
+    let query: ListQRs = match parse_query(query) {
+        Ok(query) => query,
+        Err(res) => return res,
+    };
+
+    let ids: Vec<String> = state.qrs.keys().cloned().collect();
+    for id in &ids {
+        // Expire QRs before filtering by status.
+        state.qr(id);
+    }
+
+    let items = state
+        .qrs
+        .iter()
+        .filter(|(_, qr)| query.qr_id.as_ref().is_none_or(|id| *id == qr.qr_id))
+        .filter(|(_, qr)| query.order_id.is_none() || query.order_id == qr.order_id)
+        .filter(|(_, qr)| query.r#type.as_ref().is_none_or(|t| *t == qr.r#type))
+        .filter(|(_, qr)| query.status.as_ref().is_none_or(|s| *s == qr.status))
+        .filter(|(_, qr)| {
+            query
+                .terminal_id
+                .as_ref()
+                .is_none_or(|id| *id == qr.terminal_id)
+        })
+        .collect();
+
+    return page(items, query.offset, query.count);
+}
+
+fn get_qr(state: &mut State, id: &str) -> Response {
+    return match state.qr(id) {
+        Some(qr) => ok(serde_json::to_value(&*qr).unwrap()),
+        None => errors(404, "qrNotFound", "QR not found"),
+    };
+}
+
+fn cancel_qr(state: &mut State, id: &str, req: &Request) -> Response {
+    if let Err(res) = parse_body::<CancelQR>(req) {
+        return res;
+    }
+
+    let Some(qr) = state.qr(id) else {
+        return errors(404, "qrNotFound", "QR not found");
+    };
+
+    if qr.status != QRStatus::Active {
+        return errors(400, "qrNotActive", &format!("QR is {}", qr.status));
+    }
+
+    qr.status = QRStatus::Cancelled;
+    qr.updated_at = Utc::now();
+
+    return ok(json!({ "qrId": qr.qr_id, "status": qr.status }));
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestPay {
+    qr_id: String,
+    amount: Decimal,
+    iban: String,
+    currency: Currency,
+    payer_name: String,
+}
+
+fn test_pay(state: &mut State, req: &Request) -> Response {
+    let body: TestPay = match parse_body(req) {
+        Ok(body) => body,
+        Err(res) => return res,
+    };
+
+    let Some(qr) = state.qr(&body.qr_id) else {
+        return errors(404, "qrNotFound", "QR not found");
+    };
+
+    if qr.status != QRStatus::Active {
+        return errors(400, "qrNotActive", &format!("QR is {}", qr.status));
+    }
+
+    if body.currency != qr.currency {
+        return errors(400, "invalidCurrency", "currency does not match QR");
+    }
+
+    let valid_amount = match qr.amount_type {
+        PaymentType::Fixed => body.amount == qr.amount,
+        PaymentType::Controlled => {
+            let min = qr.amount_min.unwrap_or(Decimal::ZERO);
+            let max = qr.amount_max.unwrap_or(Decimal::MAX);
+            body.amount >= min && body.amount <= max
+        }
+        _ => body.amount > Decimal::ZERO,
+    };
+
+    if !valid_amount {
+        return errors(400, "invalidAmount", "amount does not match QR");
+    }
+
+    if qr.r#type == QRType::Dynamic {
+        qr.status = QRStatus::Paid;
+    }
+    qr.updated_at = Utc::now();
+    let qr = qr.clone();
+
+    let pay_id = state.next_id();
+    let reference_id = state.next_id();
+    let extension_id = state.next_id();
+    let payment = PaymentDetails {
+        pay_id: PaymentId::new(pay_id.clone()),
+        reference_id,
+        qr_id: qr.qr_id.clone(),
+        extension_id: Some(ExtensionId::new(extension_id)),
+        order_id: qr.order_id.clone(),
+        amount: body.amount,
+        commission: Decimal::ZERO,
+        currency: body.currency,
+        description: qr.description.clone(),
+        payer_name: body.payer_name,
+        payer_iban: body.iban,
+        status: PaymentStatus::Executed,
+        executed_at: now_local(),
+        refunded_at: None,
+        refunded_amount: None,
+        terminal_id: Some(qr.terminal_id.clone()),
+    };
+
+    let notification = notification(&state.config.signature_key, &qr, &payment);
+    state.notifications.push(notification.clone());
+    if !qr.callback_url.is_empty() {
+        deliver(qr.callback_url.clone(), notification);
+    }
+
+    let response = json!({
+        "qrId": qr.qr_id,
+        "qrStatus": qr.status,
+        "orderId": qr.order_id,
+        "payId": payment.pay_id,
+        "amount": payment.amount,
+        "commission": payment.commission,
+        "currency": payment.currency,
+        "payerName": payment.payer_name,
+        "payerIban": payment.payer_iban,
+        "executedAt": payment.executed_at,
+        "referenceId": payment.reference_id,
+    });
+
+    state.payments.insert(pay_id, payment);
+
+    return ok(response);
+}
+
+fn list_payments(state: &State, query: &str) -> Response {
+    let query: ListPayments = match parse_query(query) {
+        Ok(query) => query,
+        Err(res) => return res,
+    };
+
+    let items = state
+        .payments
+        .iter()
+        .filter(|(_, p)| query.pay_id.as_ref().is_none_or(|id| *id == p.pay_id))
+        .filter(|(_, p)| query.qr_id.as_ref().is_none_or(|id| *id == p.qr_id))
+        .filter(|(_, p)| query.order_id.is_none() || query.order_id == p.order_id)
+        .filter(|(_, p)| query.status.as_ref().is_none_or(|s| *s == p.status))
+        .filter(|(_, p)| query.terminal_id.is_none() || query.terminal_id == p.terminal_id)
+        .filter(|(_, p)| {
+            let executed_at = DateTime::parse_from_rfc3339(&p.executed_at).ok();
+            let after_from = query
+                .executed_at_from
+                .is_none_or(|from| executed_at.is_some_and(|at| at >= from));
+            let before_to = query
+                .executed_at_to
+                .is_none_or(|to| executed_at.is_some_and(|at| at < to));
+            after_from && before_to
+        })
+        .collect();
+
+    return page(items, query.offset, query.count);
+}
+
+fn get_payment(state: &State, id: &str) -> Response {
+    return match state.payments.get(id) {
+        Some(payment) => ok(serde_json::to_value(payment).unwrap()),
+        None => errors(404, "paymentNotFound", "payment not found"),
+    };
+}
+
+fn refund_payment(state: &mut State, id: &str, req: &Request) -> Response {
+    let body: RefundPayment = match parse_body(req) {
+        Ok(body) => body,
+        Err(res) => return res,
+    };
+
+    let refund_id = state.next_id();
+    let Some(payment) = state.payments.get_mut(id) else {
+        return errors(404, "paymentNotFound", "payment not found");
+    };
+
+    if payment.status != PaymentStatus::Executed
+        && payment.status != PaymentStatus::PartiallyRefunded
+    {
+        return errors(
+            400,
+            "paymentNotRefundable",
+            &format!("payment is {}", payment.status),
+        );
+    }
+
+    let remaining = payment.remaining_amount();
+    let amount = body.amount.unwrap_or(remaining);
+    if amount <= Decimal::ZERO || amount > remaining {
+        return errors(
+            400,
+            "invalidAmount",
+            &format!("refund amount must be positive and at most {remaining}"),
+        );
+    }
+
+    let refunded = payment.refunded_amount.unwrap_or(Decimal::ZERO) + amount;
+    payment.refunded_amount = Some(refunded);
+    payment.refunded_at = Some(now_local());
+    payment.status = if refunded == payment.amount {
+        PaymentStatus::Refunded
+    } else {
+        PaymentStatus::PartiallyRefunded
+    };
+
+    let response = json!({
+        "payId": payment.pay_id,
+        "status": payment.status,
+        "refundId": refund_id,
+        "amount": amount,
+    });
+
+    if let Some(callback_url) = body.callback_url {
+        let mut payload = RefundNotificationPayload {
+            result: RefundNotification {
+                amount,
+                pay_id: payment.pay_id.clone(),
+                refund_id,
+                status: payment.status.clone(),
+            },
+            signature: Signature::new(String::new()),
+        };
+        payload.signature = payload.build_signature(state.config.signature_key.clone());
+        deliver(callback_url, payload);
+    }
+
+    return ok(response);
+}
+
+fn notification(
+    key: &SignatureKey,
+    qr: &GetQRDetails,
+    payment: &PaymentDetails,
+) -> NotificationPayload {
+    let mut payload = NotificationPayload {
+        result: Notification {
+            amount: payment.amount,
+            commission: payment.commission,
+            currency: payment.currency,
+            executed_at: payment.executed_at.clone(),
+            extension_id: payment
+                .extension_id
+                .clone()
+                .unwrap_or_else(|| ExtensionId::new(String::new())),
+            order_id: payment.order_id.clone(),
+            pay_id: payment.pay_id.clone(),
+            payer_iban: payment.payer_iban.clone(),
+            payer_name: payment.payer_name.clone(),
+            qr_id: qr.qr_id.clone(),
+            qr_status: qr.status.clone(),
+            reference_id: payment.reference_id.clone(),
+            terminal_id: payment.terminal_id.clone(),
+        },
+        signature: Signature::new(String::new()),
+    };
+
+    payload.signature = payload.build_signature(key.clone());
+
+    return payload;
+}
+
+/// Send notification in background, delivery errors are ignored.
+fn deliver<T: serde::Serialize + Send + 'static>(callback_url: String, payload: T) {
+    std::thread::spawn(move || {
+        let _ = reqwest::blocking::Client::new()
+            .post(callback_url)
+            .json(&payload)
+            .send();
+    });
+}
@@ -0,0 +1,195 @@
+//! Access tokens cached across clients and process restarts.
+//!
+//! Tokens are stored per [ClientId] with the absolute time they expire
+//! at, and are reused until [REFRESH_MARGIN] before that, see
+//! [Client::cached_access_token](crate::client::Client::cached_access_token).
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, Write},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    error::{Error, Result},
+    models::{response::AuthToken, AccessToken, ClientId},
+};
+
+/// Tokens expiring sooner than this are not reused.
+pub const REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredToken {
+    pub access_token: AccessToken,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl StoredToken {
+    /// Token issued just now.
+    pub fn issued(token: AuthToken) -> Self {
+        let lifetime: Duration = token.expires_in().into();
+
+        return Self {
+            expires_at: Utc::now() + lifetime,
+            access_token: token.take_access_token(),
+        };
+    }
+
+    /// Whether token is valid for at least [REFRESH_MARGIN].
+    pub fn is_fresh(&self) -> bool {
+        return self.expires_at - REFRESH_MARGIN > Utc::now();
+    }
+}
+
+/// Storage of access tokens.
+///
+/// Methods are blocking and expected to be quick, e.g. reading a small
+/// file.
+pub trait TokenStore: Send + Sync {
+    fn load(&self, client_id: &ClientId) -> Result<Option<StoredToken>>;
+
+    fn save(&self, client_id: &ClientId, token: &StoredToken) -> Result<()>;
+
+    fn remove(&self, client_id: &ClientId) -> Result<()>;
+}
+
+/// Store keeping tokens for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct InMemoryTokenStore {
+    tokens: Mutex<HashMap<ClientId, StoredToken>>,
+}
+
+impl InMemoryTokenStore {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+}
+
+impl TokenStore for InMemoryTokenStore {
+    fn load(&self, client_id: &ClientId) -> Result<Option<StoredToken>> {
+        return Ok(self.tokens.lock().unwrap().get(client_id).cloned());
+    }
+
+    fn save(&self, client_id: &ClientId, token: &StoredToken) -> Result<()> {
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.insert(client_id.clone(), token.clone());
+        return Ok(());
+    }
+
+    fn remove(&self, client_id: &ClientId) -> Result<()> {
+        self.tokens.lock().unwrap().remove(client_id);
+        return Ok(());
+    }
+}
+
+/// Store keeping tokens of all clients in a single JSON file.
+///
+/// File is created readable and writable by the owner only, on unix
+/// permissions of an existing file are narrowed to that too. Reads take
+/// a shared and writes an exclusive lock of the file, so the file can be
+/// shared by concurrent processes.
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        return Self { path: path.into() };
+    }
+
+    fn open(&self) -> Result<File> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(false);
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let file = options.open(&self.path).map_err(|err| self.error(err))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let permissions = std::fs::Permissions::from_mode(0o600);
+            file.set_permissions(permissions)
+                .map_err(|err| self.error(err))?;
+        }
+
+        return Ok(file);
+    }
+
+    fn read(&self, file: &mut File) -> Result<HashMap<ClientId, StoredToken>> {
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .map_err(|err| self.error(err))?;
+
+        if contents.trim().is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        return serde_json::from_str(&contents).map_err(|err| {
+            Error::TokenStore(format!("invalid token file {}: {err}", self.path.display()))
+        });
+    }
+
+    /// Read tokens, change them with `update` and write them back.
+    ///
+    /// Expired tokens are dropped.
+    fn update<F>(&self, update: F) -> Result<()>
+    where
+        F: FnOnce(&mut HashMap<ClientId, StoredToken>),
+    {
+        let mut file = self.open()?;
+        file.lock().map_err(|err| self.error(err))?;
+
+        let mut tokens = self.read(&mut file)?;
+        tokens.retain(|_, token| token.expires_at > Utc::now());
+        update(&mut tokens);
+
+        let contents = serde_json::to_vec(&tokens)
+            .map_err(|err| Error::Json(format!("error serializing tokens: {err}")))?;
+
+        file.rewind().map_err(|err| self.error(err))?;
+        file.set_len(0).map_err(|err| self.error(err))?;
+        file.write_all(&contents).map_err(|err| self.error(err))?;
+
+        return Ok(());
+    }
+
+    fn error(&self, err: std::io::Error) -> Error {
+        return Error::TokenStore(format!("{}: {err}", self.path.display()));
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self, client_id: &ClientId) -> Result<Option<StoredToken>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(self.error(err)),
+        };
+        file.lock_shared().map_err(|err| self.error(err))?;
+
+        let mut tokens = self.read(&mut file)?;
+        return Ok(tokens.remove(client_id));
+    }
+
+    fn save(&self, client_id: &ClientId, token: &StoredToken) -> Result<()> {
+        return self.update(|tokens| {
+            tokens.insert(client_id.clone(), token.clone());
+        });
+    }
+
+    fn remove(&self, client_id: &ClientId) -> Result<()> {
+        return self.update(|tokens| {
+            tokens.remove(client_id);
+        });
+    }
+}
@@ -0,0 +1,130 @@
+//! HTTP layer of [Client](crate::client::Client).
+//!
+//! Every request built by `Client` is passed to an [HttpTransport] as an
+//! [HttpRequest] with absolute url, headers and body already set, and
+//! the response body is read fully before it is parsed. `reqwest::Client`
+//! is the default transport, available with `reqwest` feature, TLS backend
+//! is selected with `native-tls` (default) or `rustls` features. Other
+//! HTTP clients can be plugged in with
+//! [Client::with_transport](crate::client::Client::with_transport), and
+//! `reqwest` can be dropped by building with `default-features = false`.
+//!
+//! [blocking::Client](crate::blocking::Client) does not use transports,
+//! it always sends requests with `reqwest::blocking::Client`.
+//!
+//! Transport is wrapped in a [tower::Service], see [TransportService].
+//! Layers can be stacked on top of it with
+//! [Client::with_layer](crate::client::Client::with_layer), e.g. to add
+//! headers, timeouts or retries:
+//!
+//! ```
+//! # #[cfg(feature = "reqwest")] {
+//! use maib_client::{client::Client, transport::HttpRequest};
+//!
+//! let client = Client::new("https://api.maibmerchants.md".to_owned()).with_layer(
+//!     tower::util::MapRequestLayer::new(|mut req: HttpRequest| {
+//!         req.headers_mut()
+//!             .insert("x-request-source", http::HeaderValue::from_static("billing"));
+//!         return req;
+//!     }),
+//! );
+//! # }
+//! ```
+//!
+//! Services have to keep [Error] as their error type, layers returning
+//! other errors can be adapted with [tower::util::MapErrLayer].
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use std::sync::Arc;
+
+use tower::util::BoxCloneSyncService;
+
+use crate::error::{Error, Result};
+
+pub type HttpRequest = http::Request<Vec<u8>>;
+pub type HttpResponse = http::Response<Vec<u8>>;
+
+/// Type-erased service every request of a client goes through.
+pub type Transport = BoxCloneSyncService<HttpRequest, HttpResponse, Error>;
+
+/// Future returned by [HttpTransport::send].
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<HttpResponse>> + Send + 'a>>;
+
+/// HTTP client sending requests built by [Client](crate::client::Client).
+///
+/// Errors are expected to be [Error::Http], responses with any status
+/// are returned as is.
+pub trait HttpTransport: Send + Sync + 'static {
+    fn send(&self, req: HttpRequest) -> SendFuture<'_>;
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpTransport for reqwest::Client {
+    fn send(&self, req: HttpRequest) -> SendFuture<'_> {
+        return Box::pin(async move {
+            let req = reqwest::Request::try_from(req)
+                .map_err(|err| Error::Http(format!("error building request: {err}")))?;
+
+            let res = self
+                .execute(req)
+                .await
+                .map_err(|err| Error::Http(format!("error sending request: {err}")))?;
+
+            let mut builder = http::Response::builder().status(res.status());
+            if let Some(headers) = builder.headers_mut() {
+                headers.extend(res.headers().clone());
+            }
+
+            let body = res
+                .bytes()
+                .await
+                .map_err(|err| Error::Http(format!("error reading response: {err}")))?;
+
+            return builder
+                .body(body.to_vec())
+                .map_err(|err| Error::Http(format!("error reading response: {err}")));
+        });
+    }
+}
+
+/// [tower::Service] sending requests with an [HttpTransport].
+#[derive(Debug)]
+pub struct TransportService<T> {
+    transport: Arc<T>,
+}
+
+impl<T: HttpTransport> TransportService<T> {
+    pub fn new(transport: T) -> Self {
+        return Self {
+            transport: Arc::new(transport),
+        };
+    }
+}
+
+impl<T> Clone for TransportService<T> {
+    fn clone(&self) -> Self {
+        return Self {
+            transport: self.transport.clone(),
+        };
+    }
+}
+
+impl<T: HttpTransport> tower::Service<HttpRequest> for TransportService<T> {
+    type Response = HttpResponse;
+    type Error = Error;
+    type Future = SendFuture<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        return Poll::Ready(Ok(()));
+    }
+
+    fn call(&mut self, req: HttpRequest) -> Self::Future {
+        let transport = self.transport.clone();
+        return Box::pin(async move { transport.send(req).await });
+    }
+}
@@ -0,0 +1,175 @@
+//! Polling of QR status until it reaches a final state.
+//!
+//! Complements notifications sent to `callback_url`, e.g. when payment
+//! status has to be shown to the payer.
+
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    api::MiaApi,
+    error::{Error, Result},
+    models::{response::GetQRDetails, AccessToken, QRId, QRStatus},
+};
+
+/// Shortest delay between requests, whatever the options.
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often and for how long QR is polled.
+#[derive(Debug, Clone)]
+pub struct WaitOptions {
+    /// Delay before the second request.
+    ///
+    /// Delays shorter than 100ms are raised to 100ms, so MAIB is not
+    /// polled in a tight loop.
+    pub initial_interval: Duration,
+
+    /// Upper bound of delay between requests, at least 100ms.
+    pub max_interval: Duration,
+
+    /// Delay is multiplied by this after every request.
+    ///
+    /// `0` is treated as `1`, i.e. constant delay.
+    pub backoff_factor: u32,
+
+    /// Give up with [Error::Timeout] after this long.
+    pub timeout: Option<Duration>,
+
+    /// Give up with [Error::Cancelled] once this token is cancelled.
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        return Self {
+            initial_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(10),
+            backoff_factor: 2,
+            timeout: None,
+            cancellation: None,
+        };
+    }
+}
+
+/// Poll QR until its status is final, see [QRStatus::is_final].
+///
+/// Returns the last fetched details. Errors returned by the API stop
+/// polling and are returned as is.
+pub async fn wait_for_qr_completion<A: MiaApi + Sync>(
+    api: &A,
+    qr_id: &QRId,
+    token: &AccessToken,
+    options: WaitOptions,
+) -> Result<GetQRDetails> {
+    let mut watch = core::pin::pin!(watch_qr(api, qr_id, token, options));
+    let mut last = None;
+
+    while let Some(result) = futures_util::StreamExt::next(&mut watch).await {
+        last = Some(result?);
+    }
+
+    return Ok(last.expect("stream yields at least one item"));
+}
+
+/// Poll QR, yielding details every time its status changes.
+///
+/// First fetched details are always yielded. Stream ends after a final
+/// status or an error is yielded.
+pub fn watch_qr<'a, A: MiaApi + Sync>(
+    api: &'a A,
+    qr_id: &'a QRId,
+    token: &'a AccessToken,
+    options: WaitOptions,
+) -> impl Stream<Item = Result<GetQRDetails>> + Send + 'a {
+    let state = Watch {
+        deadline: options
+            .timeout
+            .and_then(|timeout| Instant::now().checked_add(timeout)),
+        interval: None,
+        last_status: None,
+        done: false,
+        options,
+    };
+
+    return futures_util::stream::unfold(state, move |mut state| async move {
+        if state.done {
+            return None;
+        }
+
+        loop {
+            let result = state.poll(|| api.get_qr(qr_id, token)).await;
+
+            let qr = match result {
+                Ok(qr) => qr,
+                Err(err) => {
+                    state.done = true;
+                    return Some((Err(err), state));
+                }
+            };
+
+            state.done = qr.status.is_final();
+            if state.last_status.as_ref() != Some(&qr.status) {
+                state.last_status = Some(qr.status.clone());
+                return Some((Ok(qr), state));
+            }
+        }
+    });
+}
+
+struct Watch {
+    options: WaitOptions,
+    deadline: Option<Instant>,
+    /// Delay before next request, [None] before the first one.
+    interval: Option<Duration>,
+    last_status: Option<QRStatus>,
+    done: bool,
+}
+
+impl Watch {
+    /// Wait for the current interval, then send request.
+    async fn poll<F>(&mut self, request: impl FnOnce() -> F) -> Result<GetQRDetails>
+    where
+        F: core::future::Future<Output = Result<GetQRDetails>>,
+    {
+        let delay = self.interval.unwrap_or(Duration::ZERO);
+        let interval = match self.interval {
+            Some(interval) => interval
+                .checked_mul(self.options.backoff_factor.max(1))
+                .unwrap_or(self.options.max_interval)
+                .min(self.options.max_interval),
+            None => self.options.initial_interval,
+        };
+        self.interval = Some(interval.max(MIN_INTERVAL));
+
+        let step = async {
+            tokio::time::sleep(delay).await;
+            return request().await;
+        };
+
+        let cancellation = self.options.cancellation.clone();
+        let cancelled = async move {
+            match cancellation {
+                Some(token) => token.cancelled_owned().await,
+                None => core::future::pending().await,
+            }
+        };
+
+        let deadline = self.deadline;
+        let expired = async move {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => core::future::pending().await,
+            }
+        };
+
+        return tokio::select! {
+            biased;
+            _ = cancelled => Err(Error::Cancelled),
+            _ = expired => Err(Error::Timeout),
+            result = step => result,
+        };
+    }
+}
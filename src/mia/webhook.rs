@@ -0,0 +1,288 @@
+//! Receiving notifications MAIB sends to `callback_url`.
+//!
+//! [Webhook] validates signature of a notification and, if configured,
+//! rejects notifications executed too long ago and flags notifications
+//! received before, see [NotificationStore]. Events of accepted
+//! notifications can be dispatched to handlers with [Webhook::handle],
+//! which records a notification only once it was processed.
+//!
+//! Response to MAIB should have the status of [WebhookError::status],
+//! or `200 OK` if the notification was handled. MAIB delivers the
+//! notification again if the error is retryable.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    error::Error,
+    events::{EventDispatcher, HandlerError},
+    models::{
+        Notification, NotificationPayload, RefundNotificationPayload, SignatureKey,
+        ValidSignatureNotification, ValidSignatureRefundNotification,
+    },
+    notification_store::NotificationStore,
+};
+
+/// Allowed difference of MAIB and local clocks for notifications
+/// executed in the future.
+const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(5);
+
+/// Verifier of notifications received by a webhook.
+#[derive(Clone)]
+pub struct Webhook {
+    signature_key: SignatureKey,
+    store: Option<Arc<dyn NotificationStore>>,
+    max_age: Option<Duration>,
+}
+
+impl core::fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return f
+            .debug_struct("Webhook")
+            .field("store", &self.store.is_some())
+            .field("max_age", &self.max_age)
+            .finish_non_exhaustive();
+    }
+}
+
+/// Notification accepted by [Webhook].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Received {
+    pub notification: ValidSignatureNotification,
+    /// Same notification was received before, it should be
+    /// acknowledged, but not processed again.
+    ///
+    /// Always `false` without a store.
+    pub duplicate: bool,
+}
+
+/// Reason a notification was rejected by [Webhook].
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebhookError {
+    /// Signature is not valid for the signature key.
+    InvalidSignature,
+
+    /// `executedAt` is older than the max age, further in the future
+    /// than clock skew allows, or is not a valid date time.
+    Stale { executed_at: String },
+
+    /// Notification store failed, notification may be delivered again.
+    Store(Error),
+
+    /// Handler of the notification failed.
+    Handler(HandlerError),
+}
+
+impl WebhookError {
+    /// Whether MAIB should deliver the notification again.
+    pub fn is_retryable(&self) -> bool {
+        return match self {
+            WebhookError::InvalidSignature | WebhookError::Stale { .. } => false,
+            WebhookError::Store(_) => true,
+            WebhookError::Handler(err) => err.is_retryable(),
+        };
+    }
+
+    /// Status to respond to MAIB with, `503 Service Unavailable` for
+    /// retryable errors and a 4xx status otherwise.
+    pub fn status(&self) -> http::StatusCode {
+        return match self {
+            WebhookError::InvalidSignature => http::StatusCode::UNAUTHORIZED,
+            WebhookError::Stale { .. } => http::StatusCode::BAD_REQUEST,
+            WebhookError::Handler(HandlerError::Permanent(_)) => {
+                http::StatusCode::UNPROCESSABLE_ENTITY
+            }
+            WebhookError::Store(_) | WebhookError::Handler(HandlerError::Retryable(_)) => {
+                http::StatusCode::SERVICE_UNAVAILABLE
+            }
+        };
+    }
+}
+
+impl core::fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        return match self {
+            WebhookError::InvalidSignature => write!(f, "invalid signature"),
+            WebhookError::Stale { executed_at } => {
+                write!(f, "notification executed at {executed_at} is out of max age")
+            }
+            WebhookError::Store(err) => write!(f, "notification store error: {err:?}"),
+            WebhookError::Handler(err) => write!(f, "handler error: {err}"),
+        };
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+impl Webhook {
+    pub fn new(signature_key: SignatureKey) -> Self {
+        return Self {
+            signature_key,
+            store: None,
+            max_age: None,
+        };
+    }
+
+    /// Record received notifications in `store` to flag duplicates.
+    pub fn with_store(mut self, store: impl NotificationStore + 'static) -> Self {
+        self.store = Some(Arc::new(store));
+        return self;
+    }
+
+    /// Reject notifications executed more than `max_age` ago, or more
+    /// than a few minutes in the future, as their date could only be
+    /// forged.
+    ///
+    /// Notifications older than that are rejected before reaching the
+    /// store, so the store only has to keep notifications for `max_age`,
+    /// see [NotificationStore::prune].
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        return self;
+    }
+
+    /// Validate signature of `payload` and check the notification.
+    pub fn receive(&self, payload: NotificationPayload) -> Result<Received, WebhookError> {
+        let notification = payload
+            .validate_signature(self.signature_key.clone())
+            .ok_or(WebhookError::InvalidSignature)?;
+
+        return self.check(notification);
+    }
+
+    /// Check notification with an already validated signature, e.g. by
+    /// [MerchantRegistry::verify_notification](crate::merchant::MerchantRegistry::verify_notification).
+    ///
+    /// Notification is recorded in the store right away, use
+    /// [Webhook::handle_notification] to record it only once processed.
+    pub fn check(
+        &self,
+        notification: ValidSignatureNotification,
+    ) -> Result<Received, WebhookError> {
+        self.check_age(&notification.0)?;
+        let duplicate = !self.record(&notification.0)?;
+
+        return Ok(Received {
+            notification,
+            duplicate,
+        });
+    }
+
+    /// Validate signature of `payload` and dispatch its events with
+    /// `dispatcher`, see [Webhook::handle_notification].
+    pub async fn handle(
+        &self,
+        payload: NotificationPayload,
+        dispatcher: &EventDispatcher,
+    ) -> Result<Received, WebhookError> {
+        let notification = payload
+            .validate_signature(self.signature_key.clone())
+            .ok_or(WebhookError::InvalidSignature)?;
+
+        return self.handle_notification(notification, dispatcher).await;
+    }
+
+    /// Check notification with an already validated signature and
+    /// dispatch its events with `dispatcher`.
+    ///
+    /// Duplicates are not dispatched. Notification is recorded in the
+    /// store only after handlers succeed or fail with a permanent error,
+    /// so it is dispatched again when MAIB redelivers it after a
+    /// retryable error, a crash or a request dropped before handlers
+    /// completed.
+    pub async fn handle_notification(
+        &self,
+        notification: ValidSignatureNotification,
+        dispatcher: &EventDispatcher,
+    ) -> Result<Received, WebhookError> {
+        self.check_age(&notification.0)?;
+
+        if let Some(ref store) = self.store {
+            let n = &notification.0;
+            let duplicate = store
+                .contains(n.pay_id(), n.qr_status())
+                .map_err(WebhookError::Store)?;
+
+            if duplicate {
+                return Ok(Received {
+                    notification,
+                    duplicate,
+                });
+            }
+        }
+
+        if let Err(err) = dispatcher.dispatch(&notification).await {
+            if !err.is_retryable() {
+                self.record(&notification.0)?;
+            }
+            return Err(WebhookError::Handler(err));
+        }
+
+        self.record(&notification.0)?;
+
+        return Ok(Received {
+            notification,
+            duplicate: false,
+        });
+    }
+
+    /// Validate signature of refund callback `payload` and dispatch
+    /// [Event::RefundCompleted](crate::events::Event::RefundCompleted)
+    /// with `dispatcher`.
+    ///
+    /// Refund callbacks have no execution date and are not recorded in
+    /// the store, refund handlers should be idempotent.
+    pub async fn handle_refund(
+        &self,
+        payload: RefundNotificationPayload,
+        dispatcher: &EventDispatcher,
+    ) -> Result<ValidSignatureRefundNotification, WebhookError> {
+        let notification = payload
+            .validate_signature(self.signature_key.clone())
+            .ok_or(WebhookError::InvalidSignature)?;
+
+        dispatcher
+            .dispatch_refund(&notification)
+            .await
+            .map_err(WebhookError::Handler)?;
+
+        return Ok(notification);
+    }
+
+    fn check_age(&self, notification: &Notification) -> Result<(), WebhookError> {
+        let Some(max_age) = self.max_age else {
+            return Ok(());
+        };
+
+        let executed_at = notification.executed_at();
+        let stale = match DateTime::parse_from_rfc3339(executed_at) {
+            Ok(at) => {
+                let max_age = TimeDelta::from_std(max_age).unwrap_or(TimeDelta::MAX);
+                let age = Utc::now() - at.with_timezone(&Utc);
+                age > max_age || age < -MAX_CLOCK_SKEW
+            }
+            Err(_) => true,
+        };
+
+        if stale {
+            return Err(WebhookError::Stale {
+                executed_at: executed_at.to_owned(),
+            });
+        }
+
+        return Ok(());
+    }
+
+    /// Record `notification` in the store, `false` if it was recorded
+    /// before.
+    fn record(&self, notification: &Notification) -> Result<bool, WebhookError> {
+        let Some(ref store) = self.store else {
+            return Ok(true);
+        };
+
+        return store
+            .record(notification.pay_id(), notification.qr_status())
+            .map_err(WebhookError::Store);
+    }
+}
@@ -1,26 +1,58 @@
 mod sanity {
+    use rust_decimal::Decimal;
+
     use crate::models::{
-        ExtensionId, Notification, NotificationPayload, PaymentId, QRId, Signature, SignatureKey,
+        response::PaymentDetails, Currency, ExtensionId, Notification, NotificationPayload,
+        PaymentId, PaymentStatus, QRId, QRStatus, Signature, SignatureKey,
     };
 
-    fn predefined_notification() -> Notification {
+    /// Notification shared by tests, other tests change fields they
+    /// depend on.
+    pub(super) fn notification(qr_status: QRStatus, executed_at: &str) -> Notification {
         return Notification {
             amount: 0.into(),
             commission: 0.into(),
-            currency: crate::models::Currency::MDL,
-            executed_at: "2029-10-22T10:32:28+03:00".to_owned(),
+            currency: Currency::MDL,
+            executed_at: executed_at.to_owned(),
             extension_id: ExtensionId::new("extension_id".to_owned()),
             order_id: None,
             pay_id: PaymentId::new("pay_id".to_owned()),
             payer_iban: "payer_iban".to_owned(),
             payer_name: "payer_name".to_owned(),
             qr_id: QRId::new("qr_id".to_owned()),
-            qr_status: crate::models::QRStatus::Paid,
+            qr_status,
+            reference_id: "reference_id".to_owned(),
+            terminal_id: None,
+        };
+    }
+
+    /// Payment of 100 MDL shared by tests, other tests change fields
+    /// they depend on.
+    pub(super) fn payment(status: PaymentStatus) -> PaymentDetails {
+        return PaymentDetails {
+            pay_id: PaymentId::new("pay_id".to_owned()),
             reference_id: "reference_id".to_owned(),
+            qr_id: QRId::new("qr_id".to_owned()),
+            extension_id: None,
+            order_id: None,
+            amount: Decimal::from(100),
+            commission: Decimal::ZERO,
+            currency: Currency::MDL,
+            description: "foobar".to_owned(),
+            payer_name: "payer_name".to_owned(),
+            payer_iban: "payer_iban".to_owned(),
+            status,
+            executed_at: "2029-10-22T10:32:28+03:00".to_owned(),
+            refunded_at: None,
+            refunded_amount: None,
             terminal_id: None,
         };
     }
 
+    fn predefined_notification() -> Notification {
+        return notification(QRStatus::Paid, "2029-10-22T10:32:28+03:00");
+    }
+
     #[test]
     fn validate_signature() {
         let notification = predefined_notification();
@@ -92,3 +124,2361 @@ mod sanity {
         assert_eq!(sig, signature);
     }
 }
+
+#[cfg(feature = "render")]
+mod render {
+    use crate::{
+        models::{response::CreateQRResponse, QRId, QRType},
+        render::{Branding, RenderOptions, RenderQR},
+    };
+
+    fn predefined_qr() -> CreateQRResponse {
+        return CreateQRResponse {
+            qr_id: QRId::new("qr_id".to_owned()),
+            order_id: None,
+            r#type: QRType::Dynamic,
+            url: "https://maib.md/qr/qr_id".to_owned(),
+            expires_at: "2029-10-22T10:32:28+03:00".to_owned(),
+        };
+    }
+
+    #[test]
+    fn render_svg_with_branding() {
+        let options = RenderOptions {
+            branding: Some(Branding::mia()),
+            ..Default::default()
+        };
+
+        let svg = predefined_qr().to_svg(&options).unwrap();
+
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains(">MIA</text>"));
+    }
+
+    #[test]
+    fn render_png() {
+        let options = RenderOptions {
+            size: 100,
+            ..Default::default()
+        };
+
+        let png = predefined_qr().to_png(&options).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn render_terminal() {
+        let options = RenderOptions {
+            margin: 0,
+            ..Default::default()
+        };
+
+        let out = predefined_qr().to_terminal(&options).unwrap();
+        let rows = out.lines().count();
+        let columns = out.lines().next().unwrap().chars().count();
+
+        assert_eq!(rows, columns.div_ceil(2));
+    }
+}
+
+mod owned_models {
+    use rust_decimal::Decimal;
+
+    use crate::models::{
+        request::{CreateQR, OwnedCreateQR},
+        response::GetQRDetails,
+    };
+
+    #[test]
+    fn owned_create_qr_roundtrip() {
+        let request = CreateQR::new_dynamic_with_fixed_amount(
+            Decimal::from(100),
+            "2029-10-22T10:32:28+03:00",
+            "description".to_owned(),
+            "https://example.com/callback".to_owned(),
+            "https://example.com/redirect".to_owned(),
+        );
+
+        let owned = request.to_owned_request();
+        let json = serde_json::to_string(&owned).unwrap();
+        let decoded: OwnedCreateQR = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded, owned);
+        assert_eq!(decoded.as_request(), request);
+        assert_eq!(json, serde_json::to_string(&request).unwrap());
+    }
+
+    #[test]
+    fn response_roundtrip() {
+        let json = serde_json::json!({
+            "qrId": "qr_id",
+            "orderId": null,
+            "status": "Active",
+            "type": "Dynamic",
+            "url": "https://maib.md/qr/qr_id",
+            "amountType": "Fixed",
+            "currency": "MDL",
+            "amount": "100",
+            "amountMin": null,
+            "amountMax": null,
+            "description": "description",
+            "callbackUrl": "",
+            "redirectUrl": "",
+            "terminalId": "terminal_id",
+            "createdAt": "2029-10-22T07:32:28Z",
+            "updatedAt": "2029-10-22T07:32:28Z",
+            "expiresAt": "2029-10-26T07:32:28Z"
+        });
+
+        let details: GetQRDetails = serde_json::from_value(json).unwrap();
+        let encoded = serde_json::to_value(&details).unwrap();
+        let decoded: GetQRDetails = serde_json::from_value(encoded).unwrap();
+
+        assert_eq!(decoded, details);
+    }
+}
+
+mod unknown_variants {
+    use crate::models::{
+        response::{AuthToken, RefundPayment},
+        PaymentStatus, PaymentType, QRStatus, QRType, TokenType,
+    };
+
+    #[test]
+    fn known_values_parse() {
+        let status: QRStatus = serde_json::from_str(r#""Paid""#).unwrap();
+        assert_eq!(status, QRStatus::Paid);
+
+        let r#type: QRType = serde_json::from_str(r#""Hybrid""#).unwrap();
+        assert_eq!(r#type, QRType::Hybrid);
+    }
+
+    #[test]
+    fn unknown_values_parse_and_roundtrip() {
+        let status: QRStatus = serde_json::from_str(r#""Frozen""#).unwrap();
+        assert_eq!(status, QRStatus::Unknown("Frozen".to_owned()));
+        assert_eq!(status.to_string(), "Frozen");
+        assert_eq!(serde_json::to_string(&status).unwrap(), r#""Frozen""#);
+
+        let r#type: QRType = serde_json::from_str(r#""Recurring""#).unwrap();
+        assert_eq!(r#type, QRType::Unknown("Recurring".to_owned()));
+
+        let amount_type: PaymentType = serde_json::from_str(r#""Tiered""#).unwrap();
+        assert_eq!(amount_type, PaymentType::Unknown("Tiered".to_owned()));
+    }
+
+    #[test]
+    fn unknown_values_in_responses() {
+        let refund: RefundPayment = serde_json::from_value(serde_json::json!({
+            "payId": "pay_id",
+            "status": "Disputed",
+        }))
+        .unwrap();
+        assert_eq!(refund.status, PaymentStatus::Unknown("Disputed".to_owned()));
+
+        let token: AuthToken = serde_json::from_value(serde_json::json!({
+            "accessToken": "token",
+            "expiresIn": 300,
+            "tokenType": "DPoP",
+        }))
+        .unwrap();
+        assert_eq!(token.token_type(), &TokenType::Unknown("DPoP".to_owned()));
+    }
+}
+
+mod parse_response {
+    use http::StatusCode;
+
+    use crate::{endpoint::parse_response, error::Error, models::response::CancelQR};
+
+    #[test]
+    fn parses_result() {
+        let body = br#"{"result": {"qrId": "qr_id", "status": "Cancelled"}, "ok": true}"#;
+
+        let res: CancelQR = parse_response(StatusCode::OK, body).unwrap();
+
+        assert_eq!(res.qr_id, *"qr_id");
+    }
+
+    #[test]
+    fn parses_errors() {
+        let body =
+            br#"{"errors": [{"errorCode": "12001", "errorMessage": "foobar"}], "ok": false}"#;
+
+        let res = parse_response::<CancelQR>(StatusCode::BAD_REQUEST, body);
+
+        let Err(Error::Api(errors)) = res else {
+            panic!("expected api error, got {res:?}");
+        };
+        assert_eq!(errors[0].code(), "12001");
+        assert_eq!(errors[0].message(), "foobar");
+    }
+
+    #[test]
+    fn unauthorized() {
+        let res = parse_response::<CancelQR>(StatusCode::UNAUTHORIZED, b"");
+
+        assert!(matches!(res, Err(Error::Unauthorized)));
+    }
+}
+
+#[cfg(feature = "blocking")]
+mod blocking {
+    use crate::{
+        blocking::Client,
+        error::Error,
+        models::{AccessToken, QRId},
+    };
+
+    #[test]
+    fn unreachable_server() {
+        let client = Client::new("http://127.0.0.1:1".to_owned());
+        let token = AccessToken::new("token".to_owned());
+
+        let res = client.get_qr(&QRId::new("qr_id".to_owned()), &token);
+
+        assert!(matches!(res, Err(Error::Http(_))));
+    }
+}
+
+#[cfg(feature = "tracing")]
+mod tracing {
+    use std::sync::{Arc, Mutex};
+
+    use super::transport::unreachable_client;
+    use crate::models::{AccessToken, QRId};
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[tokio::test]
+    async fn request_span_does_not_leak_token() {
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(::tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = ::tracing::subscriber::set_default(subscriber);
+
+        let client = unreachable_client();
+        let token = AccessToken::new("super-secret-token".to_owned());
+        let res = client.get_qr(&QRId::new("qr_id".to_owned()), &token).await;
+        assert!(res.is_err());
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("maib.request"));
+        assert!(output.contains("/v2/mia/qr/{qr_id}"));
+        assert!(output.contains("qr_id=\"qr_id\""));
+        assert!(output.contains("error.kind=\"http\""));
+        assert!(!output.contains("super-secret-token"));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test(start_paused = true)]
+    async fn request_span_records_retries() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        use crate::{
+            rate_limit::{Rate, RateLimiter},
+            testing::InMemoryTransport,
+        };
+
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(::tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = ::tracing::subscriber::set_default(subscriber);
+
+        let calls = AtomicU32::new(0);
+        let transport = InMemoryTransport::new(move |_| {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return http::Response::builder()
+                    .status(429)
+                    .header("retry-after", "1")
+                    .body(Vec::new())
+                    .unwrap();
+            }
+
+            let body =
+                serde_json::json!({"ok": true, "result": {"qrId": "qr_id", "status": "Cancelled"}});
+            return http::Response::builder()
+                .status(200)
+                .body(serde_json::to_vec(&body).unwrap())
+                .unwrap();
+        });
+        let client =
+            crate::client::Client::with_transport("http://maib.test".to_owned(), transport)
+                .with_rate_limiter(RateLimiter::global(Rate::per_second(10)));
+
+        let token = AccessToken::new("token".to_owned());
+        let payload = crate::models::request::CancelQR {
+            reason: "foobar".to_owned(),
+        };
+        client
+            .cancel_qr(&QRId::new("qr_id".to_owned()), &payload, &token)
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("retries=1"), "{output}");
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod metrics {
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        CompositeKey,
+    };
+
+    use super::transport::unreachable_client;
+    use crate::models::{ClientId, ClientSecret, NotificationPayload, Signature, SignatureKey};
+
+    fn counter(snapshot: &[(CompositeKey, DebugValue)], name: &str, label: (&str, &str)) -> u64 {
+        return snapshot
+            .iter()
+            .filter(|(key, _)| {
+                let key = key.key();
+                key.name() == name
+                    && key
+                        .labels()
+                        .any(|l| l.key() == label.0 && l.value() == label.1)
+            })
+            .map(|(_, value)| match value {
+                DebugValue::Counter(value) => *value,
+                _ => 0,
+            })
+            .sum();
+    }
+
+    fn snapshot(recorder: &DebuggingRecorder) -> Vec<(CompositeKey, DebugValue)> {
+        return recorder
+            .snapshotter()
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key, value))
+            .collect();
+    }
+
+    #[test]
+    fn records_request_metrics() {
+        let recorder = DebuggingRecorder::new();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let client = unreachable_client();
+            let id = ClientId::new("id".to_owned());
+            let secret = ClientSecret::new("secret".to_owned());
+
+            let res = runtime.block_on(client.get_access_token(&id, &secret));
+            assert!(res.is_err());
+        });
+
+        let snapshot = snapshot(&recorder);
+        let endpoint = ("endpoint", "/v2/auth/token");
+        let kind = ("kind", "http");
+        let outcome = ("outcome", "failure");
+        assert_eq!(counter(&snapshot, "maib_requests_total", endpoint), 1);
+        assert_eq!(counter(&snapshot, "maib_request_errors_total", kind), 1);
+        assert_eq!(counter(&snapshot, "maib_token_refreshes_total", outcome), 1);
+    }
+
+    #[test]
+    fn records_webhook_verification() {
+        let recorder = DebuggingRecorder::new();
+        let payload: NotificationPayload = serde_json::from_value(serde_json::json!({
+            "result": {
+                "amount": "0",
+                "commission": "0",
+                "currency": "MDL",
+                "executedAt": "2029-10-22T10:32:28+03:00",
+                "extensionId": "extension_id",
+                "orderId": null,
+                "payId": "pay_id",
+                "payerIban": "payer_iban",
+                "payerName": "payer_name",
+                "qrId": "qr_id",
+                "qrStatus": "Paid",
+                "referenceId": "reference_id",
+                "terminalId": null
+            },
+            "signature": Signature::new("invalid".to_owned()),
+        }))
+        .unwrap();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            let valid = payload.validate_signature(SignatureKey::from("foobar".to_owned()));
+            assert!(valid.is_none());
+        });
+
+        let snapshot = snapshot(&recorder);
+        let outcome = ("outcome", "invalid");
+        assert_eq!(
+            counter(&snapshot, "maib_webhook_verifications_total", outcome),
+            1
+        );
+    }
+}
+
+#[cfg(feature = "testing")]
+mod mock {
+    use crate::{
+        api::MiaApi,
+        client::Client,
+        error::Error,
+        models::{request::CancelQR, response, AccessToken, QRId, QRStatus},
+        testing::{FakeServer, MockCall, MockMiaApi},
+    };
+
+    async fn cancel<A: MiaApi>(api: &A, qr_id: &QRId, token: &AccessToken) -> bool {
+        let payload = CancelQR {
+            reason: "foobar".to_owned(),
+        };
+
+        return match api.cancel_qr(qr_id, &payload, token).await {
+            Ok(res) => res.status == QRStatus::Cancelled,
+            Err(_) => false,
+        };
+    }
+
+    #[tokio::test]
+    async fn returns_scripted_responses_and_records_calls() {
+        let mock = MockMiaApi::new();
+        let qr_id = QRId::new("qr_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        mock.push_cancel_qr(Ok(response::CancelQR {
+            qr_id: qr_id.clone(),
+            status: QRStatus::Cancelled,
+        }))
+        .push_cancel_qr(Err(Error::Unauthorized));
+
+        assert!(cancel(&mock, &qr_id, &token).await);
+        assert!(!cancel(&mock, &qr_id, &token).await);
+
+        let expected = MockCall::CancelQR {
+            qr_id: qr_id.clone(),
+            payload: CancelQR {
+                reason: "foobar".to_owned(),
+            },
+        };
+        assert_eq!(mock.calls(), vec![expected.clone(), expected]);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "no response queued")]
+    async fn panics_without_scripted_response() {
+        let mock = MockMiaApi::new();
+        let token = AccessToken::new("token".to_owned());
+
+        let _ = mock.get_qr(&QRId::new("qr_id".to_owned()), &token).await;
+    }
+
+    #[tokio::test]
+    async fn client_futures_are_send() {
+        let server = FakeServer::start();
+        let client = std::sync::Arc::new(Client::new(server.base_url()));
+        let token = server.issue_token();
+
+        let handle = tokio::spawn(async move {
+            let qr_id = QRId::new("missing".to_owned());
+            return cancel(client.as_ref(), &qr_id, &token).await;
+        });
+
+        assert!(!handle.await.unwrap());
+    }
+}
+
+#[cfg(feature = "testing")]
+mod wait {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+    use rust_decimal::Decimal;
+    use tokio_util::sync::CancellationToken;
+
+    use crate::{
+        error::Error,
+        models::{
+            response::GetQRDetails, AccessToken, Currency, PaymentType, QRId, QRStatus, QRType,
+        },
+        testing::MockMiaApi,
+        wait::{self, WaitOptions},
+    };
+
+    fn qr(status: QRStatus) -> GetQRDetails {
+        let now = chrono::Utc::now();
+        return GetQRDetails {
+            qr_id: QRId::new("qr_id".to_owned()),
+            order_id: None,
+            status,
+            r#type: QRType::Dynamic,
+            url: "https://maib.md/qr/qr_id".to_owned(),
+            amount_type: PaymentType::Fixed,
+            currency: Currency::MDL,
+            amount: Decimal::from(100),
+            amount_min: None,
+            amount_max: None,
+            description: "foobar".to_owned(),
+            callback_url: "".to_owned(),
+            redirect_url: "".to_owned(),
+            terminal_id: "terminal_id".to_owned(),
+            created_at: now,
+            updated_at: now,
+            expires_at: now,
+        };
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn yields_status_changes_until_final_status() {
+        let mock = MockMiaApi::new();
+        mock.push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Paid)));
+        let qr_id = QRId::new("qr_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+
+        let statuses: Vec<QRStatus> = wait::watch_qr(&mock, &qr_id, &token, WaitOptions::default())
+            .map(|result| result.unwrap().status)
+            .collect()
+            .await;
+
+        assert_eq!(statuses, vec![QRStatus::Active, QRStatus::Paid]);
+        assert_eq!(mock.calls().len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backs_off_between_requests() {
+        let mock = MockMiaApi::new();
+        mock.push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Expired)));
+        let qr_id = QRId::new("qr_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let options = WaitOptions {
+            initial_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(3),
+            ..Default::default()
+        };
+
+        let started_at = tokio::time::Instant::now();
+        let qr = wait::wait_for_qr_completion(&mock, &qr_id, &token, options)
+            .await
+            .unwrap();
+
+        assert_eq!(qr.status, QRStatus::Expired);
+        // 1s, 2s, then capped at 3s.
+        assert_eq!(started_at.elapsed(), Duration::from_secs(6));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caps_overflowing_backoff() {
+        let mock = MockMiaApi::new();
+        mock.push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Expired)));
+        let qr_id = QRId::new("qr_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let options = WaitOptions {
+            initial_interval: Duration::from_secs(1),
+            max_interval: Duration::MAX,
+            backoff_factor: u32::MAX,
+            timeout: Some(Duration::MAX),
+            ..Default::default()
+        };
+
+        let qr = wait::wait_for_qr_completion(&mock, &qr_id, &token, options)
+            .await
+            .unwrap();
+
+        assert_eq!(qr.status, QRStatus::Expired);
+        assert_eq!(mock.calls().len(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_interval_with_zero_backoff_factor() {
+        let mock = MockMiaApi::new();
+        mock.push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Expired)));
+        let qr_id = QRId::new("qr_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let options = WaitOptions {
+            backoff_factor: 0,
+            ..Default::default()
+        };
+
+        let started_at = tokio::time::Instant::now();
+        wait::wait_for_qr_completion(&mock, &qr_id, &token, options)
+            .await
+            .unwrap();
+
+        assert_eq!(started_at.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn raises_zero_initial_interval() {
+        let mock = MockMiaApi::new();
+        mock.push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Expired)));
+        let qr_id = QRId::new("qr_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let options = WaitOptions {
+            initial_interval: Duration::ZERO,
+            max_interval: Duration::ZERO,
+            ..Default::default()
+        };
+
+        let started_at = tokio::time::Instant::now();
+        wait::wait_for_qr_completion(&mock, &qr_id, &token, options)
+            .await
+            .unwrap();
+
+        assert_eq!(started_at.elapsed(), Duration::from_millis(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_on_timeout() {
+        let mock = MockMiaApi::new();
+        mock.push_get_qr(Ok(qr(QRStatus::Active)))
+            .push_get_qr(Ok(qr(QRStatus::Active)));
+        let qr_id = QRId::new("qr_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let options = WaitOptions {
+            timeout: Some(Duration::from_millis(1500)),
+            ..Default::default()
+        };
+
+        let result = wait::wait_for_qr_completion(&mock, &qr_id, &token, options).await;
+
+        assert_eq!(result, Err(Error::Timeout));
+        assert_eq!(mock.calls().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_on_cancellation() {
+        let mock = MockMiaApi::new();
+        mock.push_get_qr(Ok(qr(QRStatus::Active)));
+        let qr_id = QRId::new("qr_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let cancellation = CancellationToken::new();
+        let options = WaitOptions {
+            cancellation: Some(cancellation.clone()),
+            ..Default::default()
+        };
+
+        let mut watch = std::pin::pin!(wait::watch_qr(&mock, &qr_id, &token, options));
+        assert!(watch.next().await.unwrap().is_ok());
+
+        cancellation.cancel();
+        assert_eq!(watch.next().await, Some(Err(Error::Cancelled)));
+        assert_eq!(watch.next().await, None);
+    }
+}
+
+mod refunds {
+    use rust_decimal::Decimal;
+
+    use super::sanity;
+    use crate::{
+        error::Error,
+        models::{request::RefundPayment, response::PaymentDetails, PaymentStatus},
+    };
+
+    fn payment(status: PaymentStatus, refunded_amount: Option<Decimal>) -> PaymentDetails {
+        let mut payment = sanity::payment(status);
+        payment.refunded_amount = refunded_amount;
+        return payment;
+    }
+
+    #[test]
+    fn full_refund_sends_only_reason() {
+        let payload = RefundPayment::full("foobar".to_owned());
+
+        let value = serde_json::to_value(&payload).unwrap();
+
+        assert_eq!(value, serde_json::json!({ "reason": "foobar" }));
+    }
+
+    #[test]
+    fn remaining_amount_accounts_for_refunds() {
+        assert_eq!(
+            payment(PaymentStatus::Executed, None).remaining_amount(),
+            Decimal::from(100)
+        );
+        assert_eq!(
+            payment(PaymentStatus::PartiallyRefunded, Some(Decimal::from(30))).remaining_amount(),
+            Decimal::from(70)
+        );
+        assert_eq!(
+            payment(PaymentStatus::Refunded, None).remaining_amount(),
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn refuses_refund_exceeding_remaining_amount() {
+        let payment = payment(PaymentStatus::PartiallyRefunded, Some(Decimal::from(30)));
+
+        let exact = RefundPayment::partial(Decimal::from(70), "foobar".to_owned());
+        assert_eq!(payment.check_refund(&exact), Ok(()));
+
+        let too_much = RefundPayment::partial(Decimal::from(71), "foobar".to_owned());
+        assert_eq!(
+            payment.check_refund(&too_much),
+            Err(Error::RefundExceedsRemaining {
+                requested: Decimal::from(71),
+                remaining: Decimal::from(70),
+            })
+        );
+
+        let negative = RefundPayment::partial(Decimal::from(-1), "foobar".to_owned());
+        assert!(payment.check_refund(&negative).is_err());
+    }
+}
+
+mod reconcile {
+    use rust_decimal::Decimal;
+
+    use super::sanity;
+    use crate::{
+        models::{response::PaymentDetails, Currency, PaymentId, PaymentStatus},
+        reconcile::{compare, ExpectedPayment, ExpectedStatus},
+    };
+
+    fn payment(order_id: &str, amount: i64, status: PaymentStatus) -> PaymentDetails {
+        let mut payment = sanity::payment(status);
+        payment.pay_id = PaymentId::new(format!("pay-{order_id}"));
+        payment.order_id = Some(order_id.to_owned());
+        payment.amount = Decimal::from(amount);
+        return payment;
+    }
+
+    fn expected(order_id: &str, amount: i64, status: ExpectedStatus) -> ExpectedPayment {
+        return ExpectedPayment {
+            order_id: order_id.to_owned(),
+            amount: Decimal::from(amount),
+            currency: Currency::MDL,
+            status,
+        };
+    }
+
+    #[test]
+    fn reports_every_kind_of_difference() {
+        let local = vec![
+            expected("matched", 100, ExpectedStatus::Paid),
+            expected("amount", 100, ExpectedStatus::Paid),
+            expected("status", 100, ExpectedStatus::Paid),
+            expected("only-local", 100, ExpectedStatus::Paid),
+        ];
+        let remote = vec![
+            payment("matched", 100, PaymentStatus::Executed),
+            payment("amount", 90, PaymentStatus::Executed),
+            payment("status", 100, PaymentStatus::Refunded),
+            payment("only-remote", 100, PaymentStatus::Executed),
+        ];
+
+        let report = compare(local, remote);
+
+        assert_eq!(report.matched.len(), 1);
+        assert_eq!(report.matched[0].expected.order_id, "matched");
+        assert_eq!(report.amount_mismatches.len(), 1);
+        assert_eq!(report.amount_mismatches[0].actual.amount, Decimal::from(90));
+        assert_eq!(report.status_mismatches.len(), 1);
+        assert_eq!(
+            report.status_mismatches[0].actual.status,
+            PaymentStatus::Refunded
+        );
+        assert_eq!(report.missing_at_maib[0].order_id, "only-local");
+        assert_eq!(
+            report.missing_locally[0].order_id.as_deref(),
+            Some("only-remote")
+        );
+        assert!(!report.is_clean());
+
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(value["missingAtMaib"][0]["status"], "paid");
+    }
+
+    #[test]
+    fn pairs_payments_of_same_order_in_order() {
+        let local = vec![
+            expected("order", 100, ExpectedStatus::Paid),
+            expected("order", 50, ExpectedStatus::Paid),
+        ];
+        let remote = vec![
+            payment("order", 100, PaymentStatus::Executed),
+            payment("other", 100, PaymentStatus::Executed),
+            payment("order", 50, PaymentStatus::Executed),
+            payment("order", 20, PaymentStatus::Executed),
+        ];
+
+        let report = compare(local, remote);
+
+        assert_eq!(report.matched.len(), 2);
+        assert_eq!(report.matched[1].actual.amount, Decimal::from(50));
+        let missing: Vec<Decimal> = report.missing_locally.iter().map(|p| p.amount).collect();
+        assert_eq!(missing, [Decimal::from(100), Decimal::from(20)]);
+        assert_eq!(report.missing_locally[0].order_id.as_deref(), Some("other"));
+    }
+}
+
+#[cfg(feature = "export")]
+mod export {
+    use rust_decimal::Decimal;
+
+    use super::sanity;
+    use crate::{
+        export::{
+            mask_iban, mask_name, neutralize_formula, ExportOptions, Exporter, Format, Record,
+        },
+        models::{response::PaymentDetails, PaymentId, PaymentStatus},
+    };
+
+    fn payment(pay_id: &str) -> PaymentDetails {
+        let mut payment = sanity::payment(PaymentStatus::Executed);
+        payment.pay_id = PaymentId::new(pay_id.to_owned());
+        payment.order_id = Some("order, 1".to_owned());
+        payment.amount = Decimal::new(10050, 2);
+        payment.payer_name = "John Doe".to_owned();
+        payment.payer_iban = "MD88AG000000011621810140".to_owned();
+        return payment;
+    }
+
+    #[test]
+    fn masks_pii() {
+        assert_eq!(
+            mask_iban("MD88AG000000011621810140"),
+            "MD88****************0140"
+        );
+        assert_eq!(mask_iban("short"), "*****");
+        assert_eq!(mask_name("John D."), "J*** D*");
+    }
+
+    #[test]
+    fn writes_csv_with_header() {
+        let mut exporter = Exporter::new(Vec::new(), Format::Csv, ExportOptions::default());
+        exporter.write(&payment("1")).unwrap();
+        exporter.write(&payment("2")).unwrap();
+
+        let csv = String::from_utf8(exporter.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], PaymentDetails::COLUMNS.join(","));
+        assert!(lines[1].starts_with("1,reference_id,qr_id,,\"order, 1\",100.50,0,MDL,"));
+        assert!(lines[1].contains("John Doe,MD88AG000000011621810140,Executed"));
+    }
+
+    #[test]
+    fn writes_header_without_records() {
+        let exporter: Exporter<_, PaymentDetails> =
+            Exporter::new(Vec::new(), Format::Csv, ExportOptions::default());
+
+        let csv = String::from_utf8(exporter.finish().unwrap()).unwrap();
+
+        assert_eq!(csv.trim_end(), PaymentDetails::COLUMNS.join(","));
+    }
+
+    #[test]
+    fn writes_masked_json_lines() {
+        let options = ExportOptions { mask_pii: true };
+        let mut exporter = Exporter::new(Vec::new(), Format::JsonLines, options);
+        exporter.write_all(&[payment("1"), payment("2")]).unwrap();
+
+        let output = String::from_utf8(exporter.finish().unwrap()).unwrap();
+        let rows: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1]["pay_id"], "2");
+        assert_eq!(rows[0]["payer_name"], "J*** D**");
+        assert_eq!(rows[0]["payer_iban"], "MD88****************0140");
+        assert_eq!(rows[0]["refunded_at"], serde_json::Value::Null);
+        assert_eq!(rows[0]["amount"], "100.50");
+    }
+
+    #[test]
+    fn writes_json_lines_keys_in_column_order() {
+        let mut exporter = Exporter::new(Vec::new(), Format::JsonLines, ExportOptions::default());
+        exporter.write(&payment("1")).unwrap();
+
+        let output = String::from_utf8(exporter.finish().unwrap()).unwrap();
+        let positions: Vec<usize> = PaymentDetails::COLUMNS
+            .iter()
+            .map(|column| output.find(&format!("\"{column}\":")).unwrap())
+            .collect();
+
+        assert!(positions.is_sorted(), "{output}");
+    }
+
+    #[test]
+    fn neutralizes_formulas_in_csv() {
+        let mut payment = payment("1");
+        payment.payer_name = "=HYPERLINK(\"http://evil\")".to_owned();
+        payment.reference_id = "@SUM(A1)".to_owned();
+        payment.refunded_amount = Some(Decimal::new(-5, 0));
+
+        let mut exporter = Exporter::new(Vec::new(), Format::Csv, ExportOptions::default());
+        exporter.write(&payment).unwrap();
+
+        let csv = String::from_utf8(exporter.finish().unwrap()).unwrap();
+        let row = csv.lines().nth(1).unwrap();
+
+        assert!(row.starts_with("1,'@SUM(A1),"), "{row}");
+        assert!(
+            row.contains(",\"'=HYPERLINK(\"\"http://evil\"\")\","),
+            "{row}"
+        );
+        assert!(row.contains(",-5,"), "{row}");
+        assert_eq!(neutralize_formula("-1.5"), "-1.5");
+        assert_eq!(neutralize_formula("+373"), "+373");
+        assert_eq!(neutralize_formula("-cmd"), "'-cmd");
+    }
+}
+
+#[cfg(feature = "testing")]
+mod idempotent {
+    use rust_decimal::Decimal;
+
+    use super::sanity::payment;
+    use crate::{
+        error::{ApiError, Error},
+        idempotent,
+        models::{
+            request::{CreateQR, RefundPayment},
+            response::{self, CreateQRResponse, PaymentDetails},
+            AccessToken, PaymentId, PaymentStatus, QRId, QRType,
+        },
+        testing::{MockCall, MockMiaApi},
+    };
+
+    fn partially_refunded(refunded: i64) -> PaymentDetails {
+        let mut payment = payment(PaymentStatus::PartiallyRefunded);
+        payment.refunded_amount = Some(Decimal::from(refunded));
+        return payment;
+    }
+
+    #[tokio::test]
+    async fn does_not_refund_refunded_payment() {
+        let mock = MockMiaApi::new();
+        mock.push_get_payment(Ok(payment(PaymentStatus::Refunded)));
+        let pay_id = PaymentId::new("pay_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let payload = RefundPayment::full("foobar".to_owned());
+
+        let refund = idempotent::refund_payment(&mock, &pay_id, &payload, Decimal::ZERO, &token)
+            .await
+            .unwrap();
+
+        assert_eq!(refund.status, PaymentStatus::Refunded);
+        assert_eq!(refund.amount, Some(Decimal::from(100)));
+        assert_eq!(mock.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_repeat_partial_refund() {
+        let mock = MockMiaApi::new();
+        mock.push_get_payment(Ok(partially_refunded(50)));
+        let pay_id = PaymentId::new("pay_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let payload = RefundPayment::partial(Decimal::from(20), "foobar".to_owned());
+
+        let refund =
+            idempotent::refund_payment(&mock, &pay_id, &payload, Decimal::from(30), &token)
+                .await
+                .unwrap();
+
+        assert_eq!(
+            refund,
+            response::RefundPayment {
+                pay_id,
+                status: PaymentStatus::PartiallyRefunded,
+                refund_id: None,
+                amount: Some(Decimal::from(20)),
+            }
+        );
+        assert_eq!(mock.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn refunds_payment_refunded_as_expected() {
+        let mock = MockMiaApi::new();
+        mock.push_get_payment(Ok(partially_refunded(30)))
+            .push_refund_payment(Err(Error::Unauthorized));
+        let pay_id = PaymentId::new("pay_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let payload = RefundPayment::partial(Decimal::from(20), "foobar".to_owned());
+
+        let refund =
+            idempotent::refund_payment(&mock, &pay_id, &payload, Decimal::from(30), &token).await;
+
+        assert_eq!(refund, Err(Error::Unauthorized));
+        assert_eq!(mock.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn accepts_refused_refund_made_concurrently() {
+        let refused: ApiError = serde_json::from_value(serde_json::json!({
+            "errorCode": "paymentNotRefundable",
+            "errorMessage": "payment is already refunded",
+        }))
+        .unwrap();
+        let mock = MockMiaApi::new();
+        mock.push_get_payment(Ok(partially_refunded(30)))
+            .push_refund_payment(Err(Error::Api(vec![refused.clone()])))
+            .push_get_payment(Ok(partially_refunded(50)));
+        let pay_id = PaymentId::new("pay_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let payload = RefundPayment::partial(Decimal::from(20), "foobar".to_owned());
+
+        let refund =
+            idempotent::refund_payment(&mock, &pay_id, &payload, Decimal::from(30), &token)
+                .await
+                .unwrap();
+        assert_eq!(refund.refund_id, None);
+        assert_eq!(refund.amount, Some(Decimal::from(20)));
+        assert_eq!(mock.calls().len(), 3);
+
+        mock.push_get_payment(Ok(partially_refunded(30)))
+            .push_refund_payment(Err(Error::Api(vec![refused.clone()])))
+            .push_get_payment(Ok(partially_refunded(30)));
+        let refund =
+            idempotent::refund_payment(&mock, &pay_id, &payload, Decimal::from(30), &token).await;
+        assert_eq!(refund, Err(Error::Api(vec![refused])));
+    }
+
+    #[tokio::test]
+    async fn refuses_refund_of_payment_refunded_unexpectedly() {
+        let mock = MockMiaApi::new();
+        mock.push_get_payment(Ok(partially_refunded(60)));
+        let pay_id = PaymentId::new("pay_id".to_owned());
+        let token = AccessToken::new("token".to_owned());
+        let payload = RefundPayment::partial(Decimal::from(20), "foobar".to_owned());
+
+        let refund =
+            idempotent::refund_payment(&mock, &pay_id, &payload, Decimal::from(30), &token).await;
+
+        assert_eq!(
+            refund,
+            Err(Error::RefundedAmountMismatch {
+                expected: Decimal::from(30),
+                refunded: Decimal::from(60),
+            })
+        );
+        assert_eq!(mock.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn creates_qr_without_order_id_directly() {
+        let mock = MockMiaApi::new();
+        mock.push_create_qr(Ok(CreateQRResponse {
+            qr_id: QRId::new("qr_id".to_owned()),
+            order_id: None,
+            r#type: QRType::Dynamic,
+            url: "https://maib.md/qr/qr_id".to_owned(),
+            expires_at: "2029-10-22T10:32:28+03:00".to_owned(),
+        }));
+        let payload = CreateQR::new_dynamic_with_fixed_amount(
+            Decimal::from(100),
+            "2029-10-22T10:32:28+03:00",
+            "foobar".to_owned(),
+            "".to_owned(),
+            "".to_owned(),
+        );
+        let token = AccessToken::new("token".to_owned());
+
+        idempotent::create_qr(&mock, &payload, &token)
+            .await
+            .unwrap();
+
+        let calls = mock.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(matches!(calls[0], MockCall::CreateQR { .. }));
+    }
+}
+
+mod rate_limit {
+    use std::time::Duration;
+
+    use http::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use tokio::time::Instant;
+
+    use crate::rate_limit::{Rate, RateLimiter};
+
+    #[tokio::test(start_paused = true)]
+    async fn delays_requests_over_rate() {
+        let limiter = RateLimiter::global(Rate::per_second(2));
+        let started = Instant::now();
+
+        for _ in 0..4 {
+            limiter.acquire("/v2/mia/qr").await;
+        }
+
+        // Burst of 2, then one request every 500ms.
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+        assert_eq!(limiter.budget().global, Some(0));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(limiter.budget().global, Some(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_endpoints_separately() {
+        let limiter = RateLimiter::new().with_endpoint("/v2/mia/qr", Rate::per_minute(1));
+        let started = Instant::now();
+
+        limiter.acquire("/v2/mia/qr").await;
+        limiter.acquire("/v2/mia/payments").await;
+        limiter.acquire("/v2/mia/payments").await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        let budget = limiter.budget();
+        assert_eq!(budget.global, None);
+        assert_eq!(budget.endpoints["/v2/mia/qr"], 0);
+
+        limiter.acquire("/v2/mia/qr").await;
+        assert_eq!(started.elapsed(), Duration::from_secs(60));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_queued_requests() {
+        let limiter = RateLimiter::global(Rate::per_second(1));
+        limiter.acquire("/v2/mia/qr").await;
+
+        let queued = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire("/v2/mia/qr").await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(limiter.queue_depth(), 1);
+
+        queued.await.unwrap();
+        assert_eq!(limiter.queue_depth(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn releases_slot_of_cancelled_request() {
+        let limiter = RateLimiter::global(Rate::per_second(1))
+            .with_endpoint("/v2/mia/qr", Rate::per_second(1));
+        let started = Instant::now();
+        limiter.acquire("/v2/mia/qr").await;
+
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(100), limiter.acquire("/v2/mia/qr")).await;
+        assert!(cancelled.is_err());
+        assert_eq!(limiter.queue_depth(), 0);
+
+        // Cancelled request does not delay the next one.
+        limiter.acquire("/v2/mia/qr").await;
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pauses_after_retry_after() {
+        let limiter = RateLimiter::new().with_max_retries(1);
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("5"));
+
+        assert!(limiter.throttled(&headers, 0));
+        assert!(!limiter.throttled(&headers, 1));
+        assert_eq!(limiter.budget().paused_for, Some(Duration::from_secs(5)));
+
+        let started = Instant::now();
+        limiter.acquire("/v2/mia/qr").await;
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+        assert_eq!(limiter.budget().paused_for, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caps_huge_retry_after() {
+        let limiter = RateLimiter::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("18446744073709551615"),
+        );
+
+        assert!(limiter.throttled(&headers, 0));
+        assert_eq!(
+            limiter.budget().paused_for,
+            Some(Duration::from_secs(5 * 60))
+        );
+    }
+}
+
+mod circuit_breaker {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::{
+        circuit_breaker::{CircuitBreaker, CircuitBreakerOptions, CircuitState, Transition},
+        error::Error,
+    };
+
+    fn breaker() -> (CircuitBreaker, Arc<Mutex<Vec<Transition>>>) {
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+        let options = CircuitBreakerOptions {
+            failure_ratio: 0.5,
+            window: 4,
+            minimum_requests: 4,
+            open_for: Duration::from_secs(10),
+            half_open_probes: 1,
+        };
+
+        let recorded = transitions.clone();
+        let breaker = CircuitBreaker::new(options)
+            .on_transition(move |transition| recorded.lock().unwrap().push(transition));
+
+        return (breaker, transitions);
+    }
+
+    fn send(breaker: &CircuitBreaker, failed: bool) {
+        breaker.acquire().unwrap().record(failed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn clamps_invalid_options() {
+        let breaker = CircuitBreaker::new(CircuitBreakerOptions {
+            failure_ratio: 0.0,
+            window: 2,
+            minimum_requests: 2,
+            open_for: Duration::from_secs(10),
+            half_open_probes: 0,
+        });
+
+        send(&breaker, false);
+        send(&breaker, false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        send(&breaker, true);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        send(&breaker, false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn opens_after_failure_ratio() {
+        let (breaker, transitions) = breaker();
+
+        send(&breaker, true);
+        send(&breaker, false);
+        send(&breaker, true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        send(&breaker, false);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(matches!(breaker.acquire(), Err(Error::CircuitOpen)));
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![Transition {
+                from: CircuitState::Closed,
+                to: CircuitState::Open,
+            }]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn probes_when_half_open() {
+        let (breaker, transitions) = breaker();
+        for _ in 0..4 {
+            send(&breaker, true);
+        }
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        let probe = breaker.acquire().unwrap();
+        assert!(matches!(breaker.acquire(), Err(Error::CircuitOpen)));
+        probe.record(true);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        // Dropped probe does not count either way.
+        drop(breaker.acquire().unwrap());
+        send(&breaker, false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        let states: Vec<CircuitState> = transitions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|transition| transition.to)
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                CircuitState::Open,
+                CircuitState::HalfOpen,
+                CircuitState::Open,
+                CircuitState::HalfOpen,
+                CircuitState::Closed,
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ignores_outcomes_from_before_transition() {
+        let (breaker, _) = breaker();
+        let late = breaker.acquire().unwrap();
+        for _ in 0..4 {
+            send(&breaker, true);
+        }
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let probe = breaker.acquire().unwrap();
+        late.record(false);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        probe.record(false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
+
+mod transport {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        client::Client,
+        error::Error,
+        models::{AccessToken, QRId},
+        transport::{HttpRequest, HttpResponse},
+    };
+
+    type Requests = Arc<Mutex<Vec<HttpRequest>>>;
+
+    /// Service recording requests and answering with `body`.
+    fn stub(
+        requests: Requests,
+        status: u16,
+        body: &'static str,
+    ) -> impl tower::Service<
+        HttpRequest,
+        Response = HttpResponse,
+        Error = Error,
+        Future = core::future::Ready<Result<HttpResponse, Error>>,
+    > + Clone {
+        return tower::service_fn(move |req: HttpRequest| {
+            requests.lock().unwrap().push(req);
+            let res = http::Response::builder()
+                .status(status)
+                .body(body.as_bytes().to_vec())
+                .unwrap();
+            return core::future::ready(Ok(res));
+        });
+    }
+
+    /// Client failing every request like an unreachable server.
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    pub(super) fn unreachable_client() -> Client {
+        return Client::with_service(
+            "http://127.0.0.1:1".to_owned(),
+            tower::service_fn(|_: HttpRequest| {
+                let err = Error::Http("error sending request: connection refused".to_owned());
+                return core::future::ready(Err::<HttpResponse, _>(err));
+            }),
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_requests_through_service() {
+        let requests = Requests::default();
+        let body = r#"{"ok": true, "result": {"qrId": "qr_id", "status": "Cancelled"}}"#;
+        let client = Client::with_service(
+            "https://maib.test".to_owned(),
+            stub(requests.clone(), 200, body),
+        );
+        let token = AccessToken::new("token".to_owned());
+
+        let result = client
+            .cancel_qr(
+                &QRId::new("qr_id".to_owned()),
+                &crate::models::request::CancelQR {
+                    reason: "foobar".to_owned(),
+                },
+                &token,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.qr_id, QRId::new("qr_id".to_owned()));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method(), http::Method::POST);
+        assert_eq!(
+            requests[0].uri().to_string(),
+            "https://maib.test/v2/mia/qr/qr_id/cancel"
+        );
+        assert_eq!(requests[0].headers()["authorization"], "Bearer token");
+        assert_eq!(requests[0].body(), br#"{"reason":"foobar"}"#);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn sends_requests_with_transport() {
+        let transport = crate::testing::InMemoryTransport::json(
+            200,
+            serde_json::json!({
+                "ok": true,
+                "result": {"accessToken": "token", "expiresIn": 300, "tokenType": "Bearer"},
+            }),
+        );
+        let client = Client::with_transport("https://maib.test".to_owned(), transport.clone());
+
+        let token = client
+            .get_access_token(
+                &crate::models::ClientId::new("id".to_owned()),
+                &crate::models::ClientSecret::new("secret".to_owned()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(token.access_token().as_str(), "token");
+
+        let requests = transport.take_requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].uri().path(), "/v2/auth/token");
+        assert!(transport.take_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn applies_layers_in_order() {
+        let requests = Requests::default();
+        let client = Client::with_service(
+            "https://maib.test".to_owned(),
+            stub(requests.clone(), 401, ""),
+        )
+        .with_layer(tower::util::MapRequestLayer::new(|mut req: HttpRequest| {
+            req.headers_mut()
+                .append("x-layer", http::HeaderValue::from_static("inner"));
+            return req;
+        }))
+        .with_layer(tower::util::MapRequestLayer::new(|mut req: HttpRequest| {
+            req.headers_mut()
+                .append("x-layer", http::HeaderValue::from_static("outer"));
+            return req;
+        }));
+        let token = AccessToken::new("token".to_owned());
+
+        let result = client.get_qr(&QRId::new("qr_id".to_owned()), &token).await;
+        assert_eq!(result, Err(Error::Unauthorized));
+
+        let requests = requests.lock().unwrap();
+        let values: Vec<_> = requests[0].headers().get_all("x-layer").iter().collect();
+        // Layer added last sees the request first.
+        assert_eq!(values, ["outer", "inner"]);
+    }
+}
+
+#[cfg(feature = "testing")]
+mod merchant {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use super::sanity;
+    use crate::{
+        client::Client,
+        error::Error,
+        merchant::{MerchantCredentials, MerchantRegistry},
+        models::{
+            ClientId, ClientSecret, Notification, NotificationPayload, QRId, QRStatus, Signature,
+            SignatureKey,
+        },
+        testing::InMemoryTransport,
+        transport::HttpResponse,
+    };
+
+    fn credentials(name: &str, terminal_ids: &[&str]) -> MerchantCredentials {
+        return MerchantCredentials {
+            client_id: ClientId::new(format!("{name}-id")),
+            client_secret: ClientSecret::new(format!("{name}-secret")),
+            signature_key: SignatureKey::from(format!("{name}-key")),
+            terminal_ids: terminal_ids.iter().map(|id| (*id).to_owned()).collect(),
+        };
+    }
+
+    fn json(status: u16, body: serde_json::Value) -> HttpResponse {
+        return http::Response::builder()
+            .status(status)
+            .body(serde_json::to_vec(&body).unwrap())
+            .unwrap();
+    }
+
+    /// Transport issuing tokens `token-1`, `token-2`, ... and accepting
+    /// only the latest one.
+    fn transport(issued: Arc<AtomicU32>) -> InMemoryTransport {
+        return InMemoryTransport::new(move |req| {
+            if req.uri().path() == "/v2/auth/token" {
+                let count = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                return json(
+                    200,
+                    serde_json::json!({"ok": true, "result": {
+                        "accessToken": format!("token-{count}"),
+                        "expiresIn": 300,
+                        "tokenType": "Bearer",
+                    }}),
+                );
+            }
+
+            let latest = format!("Bearer token-{}", issued.load(Ordering::SeqCst));
+            if req.headers()["authorization"] != latest.as_str() {
+                return json(401, serde_json::json!({}));
+            }
+
+            return json(
+                200,
+                serde_json::json!({"ok": true, "result": {"qrId": "qr_id", "status": "Cancelled"}}),
+            );
+        });
+    }
+
+    fn notification(terminal_id: Option<&str>) -> Notification {
+        let mut notification = sanity::notification(QRStatus::Paid, "2029-10-22T10:32:28+03:00");
+        notification.terminal_id = terminal_id.map(ToOwned::to_owned);
+        return notification;
+    }
+
+    fn signed(notification: Notification, key: &str) -> NotificationPayload {
+        let mut payload = NotificationPayload {
+            result: notification,
+            signature: Signature::new(String::new()),
+        };
+        payload.signature = payload.build_signature(SignatureKey::from(key.to_owned()));
+
+        return payload;
+    }
+
+    #[tokio::test]
+    async fn caches_token_per_merchant() {
+        let issued = Arc::new(AtomicU32::new(0));
+        let client =
+            Client::with_transport("https://maib.test".to_owned(), transport(issued.clone()));
+        let registry = MerchantRegistry::new(client)
+            .with_merchant("a", credentials("a", &[]))
+            .with_merchant("b", credentials("b", &[]));
+        let qr_id = QRId::new("qr_id".to_owned());
+
+        let a = registry.merchant("a").unwrap();
+        a.cancel_qr(
+            &qr_id,
+            &crate::models::request::CancelQR {
+                reason: "foobar".to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+        a.access_token().await.unwrap();
+        assert_eq!(issued.load(Ordering::SeqCst), 1);
+
+        // Token of `a` is no longer accepted once `b` gets its own.
+        let b = registry.merchant("b").unwrap();
+        b.access_token().await.unwrap();
+        assert_eq!(issued.load(Ordering::SeqCst), 2);
+
+        a.cancel_qr(
+            &qr_id,
+            &crate::models::request::CancelQR {
+                reason: "foobar".to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(issued.load(Ordering::SeqCst), 3);
+        assert_eq!(a.access_token().await.unwrap().as_str(), "token-3");
+
+        assert_eq!(
+            registry.merchant("c").unwrap_err(),
+            Error::UnknownMerchant("c".to_owned())
+        );
+    }
+
+    #[test]
+    fn routes_notifications() {
+        let client = Client::new("https://maib.test".to_owned());
+        let registry = MerchantRegistry::new(client)
+            .with_merchant("a", credentials("a", &["terminal-a"]))
+            .with_merchant("b", credentials("b", &["terminal-b"]));
+
+        let payload = signed(notification(Some("terminal-b")), "b-key");
+        let (key, _) = registry.verify_notification(payload, Some("a")).unwrap();
+        assert_eq!(key, "b");
+
+        let payload = signed(notification(None), "a-key");
+        let (key, _) = registry.verify_notification(payload, Some("a")).unwrap();
+        assert_eq!(key, "a");
+
+        // Terminal is unknown, callback segment is used instead.
+        let payload = signed(notification(Some("terminal-c")), "b-key");
+        let (key, _) = registry.verify_notification(payload, Some("b")).unwrap();
+        assert_eq!(key, "b");
+
+        let payload = signed(notification(None), "a-key");
+        assert!(registry.verify_notification(payload, Some("b")).is_none());
+
+        let payload = signed(notification(None), "a-key");
+        assert!(registry.verify_notification(payload, None).is_none());
+    }
+}
+
+#[cfg(feature = "config")]
+mod config {
+    use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+    use crate::{
+        config::{ConfigError, ConfigLoader},
+        models::{request::CreateQR, ClientId, ClientSecret},
+    };
+
+    fn write(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("maib-config-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        return path;
+    }
+
+    fn env(values: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let values: HashMap<String, String> = values
+            .iter()
+            .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+            .collect();
+        return move |name| values.get(name).cloned();
+    }
+
+    #[test]
+    fn loads_profile_from_file_and_env() {
+        let path = write(
+            "profile.toml",
+            r#"
+                base_url = "https://api.maibmerchants.md"
+                timeout_secs = 30
+                callback_url = "https://shop.md/callback"
+
+                [profiles.sandbox]
+                base_url = "https://sandbox.maibmerchants.md/"
+                client_id = "sandbox-id"
+            "#,
+        );
+        let loader = ConfigLoader::new().file(&path).profile("sandbox");
+
+        let config = loader
+            .load_with_env(env(&[
+                ("MAIB_CLIENT_SECRET", "secret"),
+                ("MAIB_CLIENT_ID", "env-id"),
+                ("MAIB_SANDBOX_CLIENT_ID", "sandbox-env-id"),
+                ("MAIB_PRODUCTION_CLIENT_ID", "production-env-id"),
+            ]))
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(config.profile.as_deref(), Some("sandbox"));
+        assert_eq!(config.base_url, "https://sandbox.maibmerchants.md");
+        assert_eq!(
+            config.client_id,
+            Some(ClientId::new("sandbox-env-id".to_owned()))
+        );
+        assert_eq!(
+            config.client_secret,
+            Some(ClientSecret::new("secret".to_owned()))
+        );
+        assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(config.connect_timeout, None);
+
+        let mut payload = CreateQR::new_dynamic_with_fixed_amount(
+            100.into(),
+            "2029-10-22T10:32:28+03:00",
+            "foobar".to_owned(),
+            "".to_owned(),
+            "https://shop.md/thanks".to_owned(),
+        );
+        let config = ConfigLoader::new()
+            .load_with_env(env(&[
+                ("MAIB_BASE_URL", "https://api.maibmerchants.md"),
+                ("MAIB_CALLBACK_URL", "https://shop.md/callback"),
+                ("MAIB_REDIRECT_URL", "https://shop.md/redirect"),
+            ]))
+            .unwrap();
+        config.apply_defaults(&mut payload);
+        assert_eq!(payload.callback_url, "https://shop.md/callback");
+        assert_eq!(payload.redirect_url, "https://shop.md/thanks");
+    }
+
+    #[test]
+    fn applies_defaults_and_overrides() {
+        let path = write(
+            "overrides.toml",
+            "client_id = \"file-id\"\nclient_secret = \"file-secret\"\n",
+        );
+
+        let config = ConfigLoader::new()
+            .file(&path)
+            .default_value("base_url", "https://api.maibmerchants.md")
+            .default_value("client_id", "default-id")
+            .set("client_secret", "flag-secret")
+            .load_with_env(env(&[("MAIB_CLIENT_SECRET", "env-secret")]))
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(config.base_url, "https://api.maibmerchants.md");
+        assert_eq!(config.client_id, Some(ClientId::new("file-id".to_owned())));
+        assert_eq!(
+            config.client_secret,
+            Some(ClientSecret::new("flag-secret".to_owned()))
+        );
+    }
+
+    #[test]
+    fn loads_json_file() {
+        let path = write(
+            "config.json",
+            r#"{"base_url": "https://api.maibmerchants.md", "profiles": {"production": {"connect_timeout_secs": 5}}}"#,
+        );
+
+        let config = ConfigLoader::new()
+            .file(&path)
+            .without_env()
+            .load_with_env(env(&[("MAIB_PROFILE", "production")]))
+            .unwrap();
+        assert_eq!(config.profile, None);
+        assert_eq!(config.connect_timeout, None);
+
+        let config = ConfigLoader::new()
+            .file(&path)
+            .load_with_env(env(&[("MAIB_PROFILE", "production")]))
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(config.profile.as_deref(), Some("production"));
+        assert_eq!(config.connect_timeout, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn rejects_invalid_configuration() {
+        let load = |values: &[(&str, &str)]| ConfigLoader::new().load_with_env(env(values));
+
+        assert_eq!(load(&[]).unwrap_err(), ConfigError::Missing("base_url"));
+        assert!(matches!(
+            load(&[("MAIB_BASE_URL", "api.maibmerchants.md")]),
+            Err(ConfigError::Invalid {
+                key: "base_url",
+                ..
+            })
+        ));
+        assert_eq!(
+            load(&[
+                ("MAIB_BASE_URL", "https://api.maibmerchants.md"),
+                ("MAIB_CLIENT_ID", "id"),
+            ])
+            .unwrap_err(),
+            ConfigError::Missing("client_secret")
+        );
+        assert!(matches!(
+            load(&[
+                ("MAIB_BASE_URL", "https://api.maibmerchants.md"),
+                ("MAIB_TIMEOUT_SECS", "soon"),
+            ]),
+            Err(ConfigError::Invalid {
+                key: "timeout_secs",
+                ..
+            })
+        ));
+        assert!(matches!(
+            load(&[
+                ("MAIB_BASE_URL", "https://api.maibmerchants.md"),
+                ("MAIB_TIMEOUT_SECS", "0"),
+            ]),
+            Err(ConfigError::Invalid {
+                key: "timeout_secs",
+                ..
+            })
+        ));
+
+        let path = write(
+            "unknown.toml",
+            "base_url = \"https://api.maibmerchants.md\"\nbase_path = \"/\"\n",
+        );
+        let result = ConfigLoader::new().file(&path).without_env().load();
+        assert!(matches!(result, Err(ConfigError::Parse { .. })));
+
+        let result = ConfigLoader::new().file(&path).profile("staging").load();
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(result, Err(ConfigError::Parse { .. })));
+
+        let path = write(
+            "staging.toml",
+            "base_url = \"https://api.maibmerchants.md\"\n",
+        );
+        let result = ConfigLoader::new()
+            .file(&path)
+            .profile("staging")
+            .without_env()
+            .load();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            result.unwrap_err(),
+            ConfigError::UnknownProfile("staging".to_owned())
+        );
+    }
+}
+
+#[cfg(feature = "testing")]
+mod token_store {
+    use std::{
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+    };
+
+    use chrono::{Duration, Utc};
+
+    use crate::{
+        client::Client,
+        models::{AccessToken, ClientId, ClientSecret},
+        testing::InMemoryTransport,
+        token_store::{FileTokenStore, InMemoryTokenStore, StoredToken, TokenStore},
+    };
+
+    fn path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("maib-tokens-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        return path;
+    }
+
+    fn token(value: &str, expires_in: Duration) -> StoredToken {
+        return StoredToken {
+            access_token: AccessToken::new(value.to_owned()),
+            expires_at: Utc::now() + expires_in,
+        };
+    }
+
+    /// Client issuing tokens `token-1`, `token-2`, ...
+    fn client(issued: Arc<AtomicU32>) -> Client {
+        let transport = InMemoryTransport::new(move |_| {
+            let count = issued.fetch_add(1, Ordering::SeqCst) + 1;
+            let body = serde_json::json!({"ok": true, "result": {
+                "accessToken": format!("token-{count}"),
+                "expiresIn": 300,
+                "tokenType": "Bearer",
+            }});
+            return http::Response::builder()
+                .status(200)
+                .body(serde_json::to_vec(&body).unwrap())
+                .unwrap();
+        });
+        return Client::with_transport("http://maib.test".to_owned(), transport);
+    }
+
+    #[test]
+    fn token_expiring_soon_is_not_fresh() {
+        assert!(token("token", Duration::minutes(5)).is_fresh());
+        assert!(!token("token", Duration::seconds(10)).is_fresh());
+        assert!(!token("token", Duration::seconds(-10)).is_fresh());
+    }
+
+    #[test]
+    fn file_store_keeps_tokens_per_client() {
+        let path = path("per-client");
+        let first = ClientId::new("first".to_owned());
+        let second = ClientId::new("second".to_owned());
+
+        let store = FileTokenStore::new(&path);
+        assert_eq!(store.load(&first).unwrap(), None);
+        store
+            .save(&first, &token("first-token", Duration::minutes(5)))
+            .unwrap();
+        store
+            .save(&second, &token("second-token", Duration::minutes(5)))
+            .unwrap();
+        store.remove(&second).unwrap();
+
+        let reopened = FileTokenStore::new(&path);
+        let loaded = reopened.load(&first).unwrap().unwrap();
+        assert_eq!(
+            loaded.access_token,
+            AccessToken::new("first-token".to_owned())
+        );
+        assert_eq!(reopened.load(&second).unwrap(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_store_drops_expired_tokens() {
+        let path = path("expired");
+        let expired = ClientId::new("expired".to_owned());
+        let store = FileTokenStore::new(&path);
+
+        store
+            .save(&expired, &token("old", Duration::seconds(-1)))
+            .unwrap();
+        store
+            .save(
+                &ClientId::new("other".to_owned()),
+                &token("new", Duration::minutes(5)),
+            )
+            .unwrap();
+
+        assert_eq!(store.load(&expired).unwrap(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_store_rejects_invalid_file() {
+        let path = path("invalid");
+        std::fs::write(&path, "not json").unwrap();
+
+        let err = FileTokenStore::new(&path)
+            .load(&ClientId::new("id".to_owned()))
+            .unwrap_err();
+
+        assert_eq!(err.kind(), "token_store");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_store_is_private_to_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = path("permissions");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        FileTokenStore::new(&path)
+            .save(
+                &ClientId::new("id".to_owned()),
+                &token("token", Duration::minutes(5)),
+            )
+            .unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reuses_fresh_token() {
+        let issued = Arc::new(AtomicU32::new(0));
+        let client = client(issued.clone());
+        let store = InMemoryTokenStore::new();
+        let id = ClientId::new("id".to_owned());
+        let secret = ClientSecret::new("secret".to_owned());
+
+        let first = client
+            .cached_access_token(&store, &id, &secret)
+            .await
+            .unwrap();
+        let second = client
+            .cached_access_token(&store, &id, &secret)
+            .await
+            .unwrap();
+
+        assert_eq!(first, AccessToken::new("token-1".to_owned()));
+        assert_eq!(second, first);
+        assert_eq!(issued.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn replaces_token_expiring_soon() {
+        let issued = Arc::new(AtomicU32::new(0));
+        let client = client(issued.clone());
+        let store = InMemoryTokenStore::new();
+        let id = ClientId::new("id".to_owned());
+        store
+            .save(&id, &token("stale", Duration::seconds(10)))
+            .unwrap();
+
+        let token = client
+            .cached_access_token(&store, &id, &ClientSecret::new("secret".to_owned()))
+            .await
+            .unwrap();
+
+        assert_eq!(token, AccessToken::new("token-1".to_owned()));
+        assert_eq!(store.load(&id).unwrap().unwrap().access_token, token);
+    }
+}
+
+mod webhook {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use chrono::{SecondsFormat, Utc};
+
+    use super::sanity;
+    use crate::{
+        events::{Event, EventDispatcher, HandlerError},
+        models::{
+            NotificationPayload, PaymentId, PaymentStatus, QRStatus, RefundNotification,
+            RefundNotificationPayload, Signature, SignatureKey, ValidSignatureNotification,
+            ValidSignatureRefundNotification,
+        },
+        notification_store::{InMemoryNotificationStore, NotificationStore},
+        webhook::{Webhook, WebhookError},
+    };
+
+    fn payload(qr_status: QRStatus, executed_at: &str) -> NotificationPayload {
+        let mut payload = NotificationPayload {
+            result: sanity::notification(qr_status, executed_at),
+            signature: Signature::new(String::new()),
+        };
+        payload.signature = payload.build_signature(key());
+
+        return payload;
+    }
+
+    fn key() -> SignatureKey {
+        return SignatureKey::from("key".to_owned());
+    }
+
+    fn now() -> String {
+        return Utc::now().to_rfc3339_opts(SecondsFormat::Secs, false);
+    }
+
+    #[test]
+    fn flags_redelivered_notification() {
+        let webhook = Webhook::new(key()).with_store(InMemoryNotificationStore::new());
+
+        let first = webhook.receive(payload(QRStatus::Paid, &now())).unwrap();
+        let second = webhook.receive(payload(QRStatus::Paid, &now())).unwrap();
+
+        assert!(!first.duplicate);
+        assert!(second.duplicate);
+    }
+
+    #[test]
+    fn accepts_notification_with_other_status() {
+        let webhook = Webhook::new(key()).with_store(InMemoryNotificationStore::new());
+
+        webhook.receive(payload(QRStatus::Active, &now())).unwrap();
+        let paid = webhook.receive(payload(QRStatus::Paid, &now())).unwrap();
+
+        assert!(!paid.duplicate);
+    }
+
+    #[test]
+    fn rejects_invalid_signature() {
+        let webhook = Webhook::new(SignatureKey::from("other".to_owned()));
+
+        let err = webhook
+            .receive(payload(QRStatus::Paid, &now()))
+            .unwrap_err();
+
+        assert_eq!(err, WebhookError::InvalidSignature);
+    }
+
+    #[test]
+    fn rejects_stale_notification() {
+        let store = std::sync::Arc::new(InMemoryNotificationStore::new());
+        let webhook = Webhook::new(key())
+            .with_store(store.clone())
+            .with_max_age(Duration::from_secs(300));
+
+        let err = webhook
+            .receive(payload(QRStatus::Paid, "2020-10-22T10:32:28+03:00"))
+            .unwrap_err();
+        assert!(matches!(err, WebhookError::Stale { .. }), "{err:?}");
+
+        let invalid = webhook.receive(payload(QRStatus::Paid, "yesterday"));
+        assert!(matches!(invalid, Err(WebhookError::Stale { .. })));
+
+        let pay_id = PaymentId::new("pay_id".to_owned());
+        assert!(store.record(&pay_id, &QRStatus::Paid).unwrap());
+    }
+
+    #[test]
+    fn rejects_notification_from_future() {
+        let webhook = Webhook::new(key()).with_max_age(Duration::from_secs(300));
+        let at = |offset: chrono::Duration| {
+            return (Utc::now() + offset).to_rfc3339_opts(SecondsFormat::Secs, false);
+        };
+
+        let err = webhook
+            .receive(payload(QRStatus::Paid, &at(chrono::Duration::hours(1))))
+            .unwrap_err();
+        assert!(matches!(err, WebhookError::Stale { .. }), "{err:?}");
+
+        let skewed = webhook.receive(payload(QRStatus::Paid, &at(chrono::Duration::minutes(1))));
+        assert!(skewed.is_ok());
+    }
+
+    #[test]
+    fn accepts_old_notification_without_max_age() {
+        let webhook = Webhook::new(key());
+
+        let received = webhook
+            .receive(payload(QRStatus::Paid, "2020-10-22T10:32:28+03:00"))
+            .unwrap();
+
+        assert!(!received.duplicate);
+    }
+
+    #[test]
+    fn prunes_old_notifications() {
+        let store = InMemoryNotificationStore::new();
+        let pay_id = PaymentId::new("pay_id".to_owned());
+        store.record(&pay_id, &QRStatus::Paid).unwrap();
+
+        store
+            .prune(Utc::now() - chrono::Duration::hours(1))
+            .unwrap();
+        assert!(!store.record(&pay_id, &QRStatus::Paid).unwrap());
+
+        store
+            .prune(Utc::now() + chrono::Duration::hours(1))
+            .unwrap();
+        assert!(store.record(&pay_id, &QRStatus::Paid).unwrap());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store_keeps_notifications_across_connections() {
+        use crate::notification_store::SqliteNotificationStore;
+
+        let path =
+            std::env::temp_dir().join(format!("maib-notifications-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pay_id = PaymentId::new("pay_id".to_owned());
+
+        let store = SqliteNotificationStore::open(&path).unwrap();
+        assert!(!store.contains(&pay_id, &QRStatus::Paid).unwrap());
+        assert!(store.record(&pay_id, &QRStatus::Paid).unwrap());
+        assert!(store.contains(&pay_id, &QRStatus::Paid).unwrap());
+        assert!(!store.record(&pay_id, &QRStatus::Paid).unwrap());
+        assert!(store.record(&pay_id, &QRStatus::Active).unwrap());
+        drop(store);
+
+        let reopened = SqliteNotificationStore::open(&path).unwrap();
+        assert!(!reopened.record(&pay_id, &QRStatus::Paid).unwrap());
+        reopened
+            .prune(Utc::now() + chrono::Duration::hours(1))
+            .unwrap();
+        assert!(reopened.record(&pay_id, &QRStatus::Paid).unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn recorder(
+        events: &Arc<Mutex<Vec<&'static str>>>,
+        name: &'static str,
+        result: Result<(), HandlerError>,
+    ) -> impl Fn(ValidSignatureNotification) -> std::future::Ready<Result<(), HandlerError>>
+           + Send
+           + Sync
+           + 'static {
+        let events = events.clone();
+        return move |_| {
+            events.lock().unwrap().push(name);
+            return std::future::ready(result.clone());
+        };
+    }
+
+    fn dispatcher(events: &Arc<Mutex<Vec<&'static str>>>) -> EventDispatcher {
+        return EventDispatcher::new()
+            .on_qr_paid(recorder(events, "paid", Ok(())))
+            .on_payment_executed(recorder(events, "executed", Ok(())))
+            .on_qr_expired(recorder(events, "expired", Ok(())));
+    }
+
+    #[tokio::test]
+    async fn dispatches_events_of_status() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let webhook = Webhook::new(key());
+        let dispatcher = dispatcher(&events);
+
+        for status in [
+            QRStatus::Paid,
+            QRStatus::Active,
+            QRStatus::Expired,
+            QRStatus::Cancelled,
+        ] {
+            webhook
+                .handle(payload(status, &now()), &dispatcher)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["executed", "paid", "executed", "expired", "expired"]
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatches_single_event() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let received = Webhook::new(key())
+            .receive(payload(QRStatus::Paid, &now()))
+            .unwrap();
+
+        dispatcher(&events)
+            .dispatch_event(Event::QrPaid, &received.notification)
+            .await
+            .unwrap();
+
+        assert_eq!(*events.lock().unwrap(), ["paid"]);
+    }
+
+    #[tokio::test]
+    async fn dispatches_refund_callback() {
+        let mut payload = RefundNotificationPayload {
+            result: RefundNotification {
+                amount: 20.into(),
+                pay_id: PaymentId::new("pay_id".to_owned()),
+                refund_id: "refund_id".to_owned(),
+                status: PaymentStatus::PartiallyRefunded,
+            },
+            signature: Signature::new(String::new()),
+        };
+        payload.signature = payload.build_signature(key());
+        let refunds = Arc::new(Mutex::new(Vec::new()));
+        let recorded = refunds.clone();
+        let dispatcher = EventDispatcher::new().on_refund_completed(
+            move |refund: ValidSignatureRefundNotification| {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push(refund.0.refund_id().to_owned());
+                return std::future::ready(Ok(()));
+            },
+        );
+
+        let refund = Webhook::new(key())
+            .handle_refund(payload.clone(), &dispatcher)
+            .await
+            .unwrap();
+        assert_eq!(refund.0.amount(), 20.into());
+        assert_eq!(*refunds.lock().unwrap(), ["refund_id"]);
+
+        let err = Webhook::new(SignatureKey::from("other".to_owned()))
+            .handle_refund(payload, &dispatcher)
+            .await
+            .unwrap_err();
+        assert_eq!(err, WebhookError::InvalidSignature);
+        assert_eq!(refunds.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn skips_duplicate_notification() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let webhook = Webhook::new(key()).with_store(InMemoryNotificationStore::new());
+        let dispatcher = dispatcher(&events);
+
+        webhook
+            .handle(payload(QRStatus::Expired, &now()), &dispatcher)
+            .await
+            .unwrap();
+        let duplicate = webhook
+            .handle(payload(QRStatus::Expired, &now()), &dispatcher)
+            .await
+            .unwrap();
+
+        assert!(duplicate.duplicate);
+        assert_eq!(*events.lock().unwrap(), ["expired"]);
+    }
+
+    #[tokio::test]
+    async fn redispatches_after_retryable_error() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let webhook = Webhook::new(key()).with_store(InMemoryNotificationStore::new());
+        let failing = EventDispatcher::new().on_qr_paid(recorder(
+            &events,
+            "failed",
+            Err(HandlerError::retryable("database is down")),
+        ));
+
+        let err = webhook
+            .handle(payload(QRStatus::Paid, &now()), &failing)
+            .await
+            .unwrap_err();
+        assert!(err.is_retryable());
+        assert_eq!(err.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+
+        let redelivered = webhook
+            .handle(payload(QRStatus::Paid, &now()), &dispatcher(&events))
+            .await
+            .unwrap();
+        assert!(!redelivered.duplicate);
+        assert_eq!(*events.lock().unwrap(), ["failed", "executed", "paid"]);
+    }
+
+    #[tokio::test]
+    async fn redispatches_after_dropped_handler() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let webhook = Webhook::new(key()).with_store(InMemoryNotificationStore::new());
+        let hanging = EventDispatcher::new().on_qr_paid(|_| std::future::pending());
+
+        let timeout = tokio::time::timeout(
+            Duration::from_millis(10),
+            webhook.handle(payload(QRStatus::Paid, &now()), &hanging),
+        )
+        .await;
+        assert!(timeout.is_err());
+
+        let redelivered = webhook
+            .handle(payload(QRStatus::Paid, &now()), &dispatcher(&events))
+            .await
+            .unwrap();
+        assert!(!redelivered.duplicate);
+        assert_eq!(*events.lock().unwrap(), ["executed", "paid"]);
+    }
+
+    #[tokio::test]
+    async fn stops_at_permanent_error() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let webhook = Webhook::new(key()).with_store(InMemoryNotificationStore::new());
+        let dispatcher = EventDispatcher::new()
+            .on_payment_executed(recorder(
+                &events,
+                "failed",
+                Err(HandlerError::permanent("unknown order")),
+            ))
+            .on_qr_paid(recorder(&events, "paid", Ok(())));
+
+        let err = webhook
+            .handle(payload(QRStatus::Paid, &now()), &dispatcher)
+            .await
+            .unwrap_err();
+        assert!(!err.is_retryable());
+        assert_eq!(err.status(), http::StatusCode::UNPROCESSABLE_ENTITY);
+
+        let redelivered = webhook
+            .handle(payload(QRStatus::Paid, &now()), &dispatcher)
+            .await
+            .unwrap();
+        assert!(redelivered.duplicate);
+        assert_eq!(*events.lock().unwrap(), ["failed"]);
+    }
+}
@@ -0,0 +1,50 @@
+use maib_client::{client::Client, error::Error, models::ClientSecret, testing::FakeServer};
+
+#[tokio::test]
+pub async fn should_issue_token_for_valid_credentials() {
+    let server = FakeServer::start();
+    let config = server.config();
+    let client = Client::new(server.base_url());
+
+    let token = client
+        .get_access_token(&config.client_id, &config.client_secret)
+        .await
+        .unwrap();
+
+    // QR does not exist, but token is accepted.
+    let qr = client
+        .get_qr(
+            &maib_client::models::QRId::new("missing".to_owned()),
+            token.access_token(),
+        )
+        .await;
+    assert!(matches!(qr, Err(Error::Http(_))));
+}
+
+#[tokio::test]
+pub async fn should_reject_invalid_credentials() {
+    let server = FakeServer::start();
+    let config = server.config();
+    let client = Client::new(server.base_url());
+    let secret = ClientSecret::new("wrong".to_owned());
+
+    let result = client.get_access_token(&config.client_id, &secret).await;
+
+    let Err(Error::Api(errors)) = result else {
+        panic!("expected api error, got {result:?}");
+    };
+    assert_eq!(errors[0].code(), "invalidCredentials");
+}
+
+#[tokio::test]
+pub async fn should_reject_unknown_token() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = maib_client::models::AccessToken::new("unknown".to_owned());
+
+    let result = client
+        .get_qr(&maib_client::models::QRId::new("qr".to_owned()), &token)
+        .await;
+
+    assert!(matches!(result, Err(Error::Unauthorized)));
+}
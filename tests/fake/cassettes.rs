@@ -0,0 +1,85 @@
+use chrono::{Duration, Utc};
+use maib_client::{
+    client::Client,
+    error::Error,
+    models::{request::CreateQR, QRId, QRStatus},
+    testing::{Cassette, CassetteServer, FakeServer, FakeServerConfig, SCRUBBED},
+};
+use rust_decimal::Decimal;
+
+fn cassette_path(name: &str) -> std::path::PathBuf {
+    return std::env::temp_dir().join(format!("maib-{name}-{}.json", std::process::id()));
+}
+
+async fn create_and_get_qr(client: &Client, config: &FakeServerConfig) -> QRId {
+    let token = client
+        .get_access_token(&config.client_id, &config.client_secret)
+        .await
+        .unwrap();
+
+    let payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        "2030-01-01T00:00:00+00:00",
+        "foobar".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+    );
+    let qr_id = client
+        .create_qr(&payload, token.access_token())
+        .await
+        .unwrap()
+        .qr_id;
+
+    let qr = client.get_qr(&qr_id, token.access_token()).await.unwrap();
+    assert_eq!(qr.status, QRStatus::Active);
+
+    return qr_id;
+}
+
+#[tokio::test]
+pub async fn should_replay_recorded_interactions() {
+    let path = cassette_path("replay");
+    let server = FakeServer::start();
+    let config = server.config();
+
+    let recorder = CassetteServer::record(&path, server.base_url()).unwrap();
+    let recorded_qr_id = create_and_get_qr(&Client::new(recorder.base_url()), &config).await;
+    recorder.finish().unwrap();
+    drop(server);
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains("fake-client-secret"));
+    assert!(contents.contains(SCRUBBED));
+    assert_eq!(Cassette::load(&path).unwrap().interactions.len(), 3);
+
+    let player = CassetteServer::replay(&path).unwrap();
+    let replayed_qr_id = create_and_get_qr(&Client::new(player.base_url()), &config).await;
+    player.finish().unwrap();
+
+    assert_eq!(recorded_qr_id, replayed_qr_id);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+pub async fn should_fail_on_unmatched_request() {
+    let path = cassette_path("unmatched");
+    Cassette::default().save(&path).unwrap();
+
+    let player = CassetteServer::replay(&path).unwrap();
+    let client = Client::new(player.base_url());
+    let expires_at = (Utc::now() + Duration::days(1)).to_rfc3339();
+    let payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        &expires_at,
+        "foobar".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+    );
+    let token = maib_client::models::AccessToken::new("token".to_owned());
+
+    let result = client.create_qr(&payload, &token).await;
+
+    assert!(matches!(result, Err(Error::Api(_))));
+    assert!(player.finish().is_err());
+    std::fs::remove_file(&path).unwrap();
+}
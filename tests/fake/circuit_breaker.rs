@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use maib_client::{
+    circuit_breaker::{CircuitBreaker, CircuitBreakerOptions, CircuitState},
+    client::Client,
+    error::Error,
+    models::{request::ListQRs, QRId},
+    testing::FakeServer,
+};
+
+#[tokio::test]
+pub async fn should_fail_fast_while_unreachable() {
+    let options = CircuitBreakerOptions {
+        minimum_requests: 2,
+        ..Default::default()
+    };
+    let breaker = CircuitBreaker::new(options);
+    let server = FakeServer::start();
+    let token = server.issue_token();
+    // Nothing listens on port 1.
+    let client = Client::new("http://127.0.0.1:1".to_owned()).with_circuit_breaker(breaker);
+
+    for _ in 0..2 {
+        let result = client.list_qrs(&ListQRs::default(), &token).await;
+        assert!(matches!(result, Err(Error::Http(_))));
+    }
+
+    let result = client.list_qrs(&ListQRs::default(), &token).await;
+    assert_eq!(result, Err(Error::CircuitOpen));
+    assert_eq!(
+        client.circuit_breaker().unwrap().state(),
+        CircuitState::Open
+    );
+}
+
+#[tokio::test]
+pub async fn should_not_count_client_errors_as_failures() {
+    let options = CircuitBreakerOptions {
+        minimum_requests: 1,
+        open_for: Duration::from_secs(60),
+        ..Default::default()
+    };
+    let server = FakeServer::start();
+    let token = server.issue_token();
+    let client = Client::new(server.base_url()).with_circuit_breaker(CircuitBreaker::new(options));
+    let missing = QRId::new("missing".to_owned());
+
+    for _ in 0..3 {
+        let result = client.get_qr(&missing, &token).await;
+        assert!(matches!(result, Err(Error::Http(_))));
+    }
+
+    assert_eq!(
+        client.circuit_breaker().unwrap().state(),
+        CircuitState::Closed
+    );
+}
@@ -0,0 +1,139 @@
+use std::process::{Command, Output};
+
+use maib_client::testing::FakeServer;
+
+use crate::transitions::pay;
+
+fn maib(server: &FakeServer, args: &[&str]) -> Output {
+    return Command::new(env!("CARGO_BIN_EXE_maib"))
+        .args(args)
+        .env_clear()
+        .env("MAIB_BASE_URL", server.base_url())
+        .env("MAIB_CLIENT_ID", "fake-client-id")
+        .env("MAIB_CLIENT_SECRET", "fake-client-secret")
+        .output()
+        .unwrap();
+}
+
+fn json(output: &Output) -> serde_json::Value {
+    assert!(output.status.success(), "{output:?}");
+    return serde_json::from_slice(&output.stdout).unwrap();
+}
+
+#[test]
+pub fn should_create_and_list_qrs() {
+    let server = FakeServer::start();
+
+    let created = json(&maib(
+        &server,
+        &["qr", "create", "--amount", "100", "--description", "foobar"],
+    ));
+    let qr_id = created["qrId"].as_str().unwrap();
+
+    let qr = json(&maib(&server, &["qr", "get", qr_id]));
+    assert_eq!(qr["status"], "Active");
+
+    let output = maib(&server, &["qr", "list", "--output", "table"]);
+    assert!(output.status.success(), "{output:?}");
+    let table = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("qrId"));
+    assert!(lines[1].contains(qr_id));
+}
+
+#[test]
+pub fn should_fail_with_api_error() {
+    let server = FakeServer::start();
+
+    let output = maib(&server, &["payment", "get", "missing"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error:"));
+}
+
+#[tokio::test]
+pub async fn should_verify_webhook_file() {
+    let server = FakeServer::start();
+    let created = json(&maib(
+        &server,
+        &["qr", "create", "--amount", "100", "--description", "foobar"],
+    ));
+    let qr_id = maib_client::models::QRId::new(created["qrId"].as_str().unwrap().to_owned());
+    let pay_id = pay(&server, &qr_id, &server.issue_token()).await;
+
+    let path = std::env::temp_dir().join(format!("maib-webhook-{}.json", std::process::id()));
+    std::fs::write(
+        &path,
+        serde_json::to_vec(&server.notifications()[0]).unwrap(),
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_maib"))
+        .args(["verify-webhook", path])
+        .env_clear()
+        .env("MAIB_SIGNATURE_KEY", "fake-signature-key")
+        .output()
+        .unwrap();
+    assert_eq!(json(&output)["payId"], pay_id.as_str());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_maib"))
+        .args(["verify-webhook", path, "--signature-key", "wrong"])
+        .env_clear()
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+pub fn should_reuse_cached_token() {
+    let server = FakeServer::start();
+    let path = std::env::temp_dir().join(format!("maib-token-cache-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let cache = path.to_str().unwrap();
+
+    json(&maib(&server, &["qr", "list", "--token-cache", cache]));
+    let cached = std::fs::read_to_string(&path).unwrap();
+    json(&maib(&server, &["qr", "list", "--token-cache", cache]));
+
+    assert!(cached.contains("fake-client-id"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), cached);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+pub fn should_load_profile_from_config_file() {
+    let server = FakeServer::start();
+    let path = std::env::temp_dir().join(format!("maib-cli-config-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        format!(
+            "base_url = \"http://127.0.0.1:1\"\n\n[profiles.fake]\nbase_url = \"{}\"\nclient_id = \"fake-client-id\"\n",
+            server.base_url()
+        ),
+    )
+    .unwrap();
+    let config = path.to_str().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_maib"))
+        .args(["qr", "list", "--config", config, "--profile", "fake"])
+        .env_clear()
+        .env("MAIB_FAKE_CLIENT_SECRET", "fake-client-secret")
+        .output()
+        .unwrap();
+    assert_eq!(json(&output)["totalCount"], 0);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_maib"))
+        .args(["qr", "list", "--config", config])
+        .env_clear()
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("client_id is not set"));
+}
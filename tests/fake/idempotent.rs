@@ -0,0 +1,92 @@
+use chrono::{Duration, Utc};
+use maib_client::{
+    client::Client,
+    error::Error,
+    models::request::{CancelQR, CreateQR},
+    testing::FakeServer,
+};
+use rust_decimal::Decimal;
+
+fn payload<'a>(expires_at: &'a str, order_id: &'a str, amount: i64) -> CreateQR<'a> {
+    let mut payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(amount),
+        expires_at,
+        "foobar".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+    );
+    payload.order_id = Some(order_id);
+
+    return payload;
+}
+
+#[tokio::test]
+pub async fn should_return_existing_qr_for_same_order() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+    let expires_at = (Utc::now() + Duration::days(1)).to_rfc3339();
+    let retry_expires_at = (Utc::now() + Duration::days(2)).to_rfc3339();
+
+    let first = client
+        .create_qr_idempotent(&payload(&expires_at, "order-1", 100), &token)
+        .await
+        .unwrap();
+    let retry = client
+        .create_qr_idempotent(&payload(&retry_expires_at, "order-1", 100), &token)
+        .await
+        .unwrap();
+    let other = client
+        .create_qr_idempotent(&payload(&expires_at, "order-2", 100), &token)
+        .await
+        .unwrap();
+
+    assert_eq!(first.qr_id, retry.qr_id);
+    assert_eq!(first.url, retry.url);
+    assert_ne!(first.qr_id, other.qr_id);
+}
+
+#[tokio::test]
+pub async fn should_conflict_when_parameters_differ() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+    let expires_at = (Utc::now() + Duration::days(1)).to_rfc3339();
+
+    let first = client
+        .create_qr_idempotent(&payload(&expires_at, "order-1", 100), &token)
+        .await
+        .unwrap();
+    let result = client
+        .create_qr_idempotent(&payload(&expires_at, "order-1", 200), &token)
+        .await;
+
+    assert_eq!(result, Err(Error::Conflict(first.qr_id)));
+}
+
+#[tokio::test]
+pub async fn should_create_new_qr_once_previous_is_not_active() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+    let expires_at = (Utc::now() + Duration::days(1)).to_rfc3339();
+
+    let first = client
+        .create_qr_idempotent(&payload(&expires_at, "order-1", 100), &token)
+        .await
+        .unwrap();
+    let reason = CancelQR {
+        reason: "foobar".to_owned(),
+    };
+    client
+        .cancel_qr(&first.qr_id, &reason, &token)
+        .await
+        .unwrap();
+
+    let second = client
+        .create_qr_idempotent(&payload(&expires_at, "order-1", 200), &token)
+        .await
+        .unwrap();
+
+    assert_ne!(first.qr_id, second.qr_id);
+}
@@ -0,0 +1,85 @@
+use chrono::{Duration, Utc};
+use maib_client::{
+    client::Client,
+    models::{
+        request::{CancelQR, CreateQR, ListPayments, ListQRs},
+        AccessToken, QRId, QRStatus,
+    },
+    testing::FakeServer,
+};
+use rust_decimal::Decimal;
+
+use crate::transitions::pay;
+
+async fn create_qr(client: &Client, token: &AccessToken, order_id: &str) -> QRId {
+    let expires_at = (Utc::now() + Duration::days(1)).to_rfc3339();
+    let mut payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        &expires_at,
+        "foobar".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+    );
+    payload.order_id = Some(order_id);
+
+    return client.create_qr(&payload, token).await.unwrap().qr_id;
+}
+
+#[tokio::test]
+pub async fn should_list_qrs_with_filters_and_pages() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let first = create_qr(&client, &token, "order-1").await;
+    let second = create_qr(&client, &token, "order-2").await;
+    let third = create_qr(&client, &token, "order-3").await;
+    let reason = CancelQR {
+        reason: "foobar".to_owned(),
+    };
+    client.cancel_qr(&second, &reason, &token).await.unwrap();
+
+    let all = client.list_qrs(&ListQRs::default(), &token).await.unwrap();
+    assert_eq!(all.total_count, 3);
+    let ids: Vec<QRId> = all.items.into_iter().map(|qr| qr.qr_id).collect();
+    assert_eq!(ids, vec![first.clone(), second.clone(), third.clone()]);
+
+    let active = ListQRs {
+        status: Some(QRStatus::Active),
+        offset: Some(1),
+        count: Some(1),
+        ..Default::default()
+    };
+    let page = client.list_qrs(&active, &token).await.unwrap();
+    assert_eq!(page.total_count, 2);
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].qr_id, third);
+
+    let by_order = ListQRs {
+        order_id: Some("order-1".to_owned()),
+        ..Default::default()
+    };
+    let page = client.list_qrs(&by_order, &token).await.unwrap();
+    assert_eq!(page.items[0].qr_id, first);
+}
+
+#[tokio::test]
+pub async fn should_list_payments_of_qr() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let qr_id = create_qr(&client, &token, "order-1").await;
+    let other_qr_id = create_qr(&client, &token, "order-2").await;
+    let pay_id = pay(&server, &qr_id, &token).await;
+    pay(&server, &other_qr_id, &token).await;
+
+    let query = ListPayments {
+        qr_id: Some(qr_id),
+        ..Default::default()
+    };
+    let page = client.list_payments(&query, &token).await.unwrap();
+
+    assert_eq!(page.total_count, 1);
+    assert_eq!(page.items[0].pay_id, pay_id);
+}
@@ -0,0 +1,12 @@
+mod auth;
+mod cassettes;
+mod circuit_breaker;
+#[cfg(feature = "cli")]
+mod cli;
+mod idempotent;
+mod lists;
+mod merchants;
+mod notifications;
+mod rate_limit;
+mod reconcile;
+mod transitions;
@@ -0,0 +1,64 @@
+use chrono::{Duration, Utc};
+use maib_client::{
+    client::Client,
+    error::Error,
+    merchant::{MerchantCredentials, MerchantRegistry},
+    models::{request::CreateQR, ClientSecret, SignatureKey},
+    testing::FakeServer,
+};
+use rust_decimal::Decimal;
+
+use crate::transitions::pay;
+
+#[tokio::test]
+pub async fn should_call_and_route_notifications_per_merchant() {
+    let server = FakeServer::start();
+    let config = server.config();
+    let registry = MerchantRegistry::new(Client::new(server.base_url()))
+        .with_merchant(
+            "shop",
+            MerchantCredentials {
+                client_id: config.client_id.clone(),
+                client_secret: config.client_secret.clone(),
+                signature_key: config.signature_key.clone(),
+                terminal_ids: Vec::new(),
+            },
+        )
+        .with_merchant(
+            "other",
+            MerchantCredentials {
+                client_id: config.client_id.clone(),
+                client_secret: ClientSecret::new("wrong".to_owned()),
+                signature_key: SignatureKey::from("other-key".to_owned()),
+                terminal_ids: Vec::new(),
+            },
+        );
+
+    let expires_at = (Utc::now() + Duration::days(1)).to_rfc3339();
+    let payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        &expires_at,
+        "foobar".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+    );
+
+    let shop = registry.merchant("shop").unwrap();
+    let qr = shop.create_qr(&payload).await.unwrap();
+    assert!(shop.get_qr(&qr.qr_id).await.is_ok());
+
+    let other = registry.merchant("other").unwrap();
+    assert!(matches!(other.get_qr(&qr.qr_id).await, Err(Error::Api(_))));
+
+    let token = shop.access_token().await.unwrap();
+    pay(&server, &qr.qr_id, &token).await;
+    let notification = server.notifications().pop().unwrap();
+
+    assert!(registry
+        .verify_notification(notification.clone(), Some("other"))
+        .is_none());
+    let (key, _) = registry
+        .verify_notification(notification, Some("shop"))
+        .unwrap();
+    assert_eq!(key, "shop");
+}
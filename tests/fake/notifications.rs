@@ -0,0 +1,152 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    time::Duration,
+};
+
+use chrono::Utc;
+use maib_client::{
+    client::Client,
+    models::{
+        request::{CreateQR, RefundPayment},
+        NotificationPayload, PaymentStatus, QRStatus, RefundNotificationPayload,
+    },
+    notification_store::InMemoryNotificationStore,
+    testing::FakeServer,
+    webhook::Webhook,
+};
+use rust_decimal::Decimal;
+
+use crate::transitions::pay;
+
+/// Accept a single request and return its body.
+fn receive_one(listener: TcpListener) -> Vec<u8> {
+    let (mut stream, _) = listener.accept().unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end().to_ascii_lowercase();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap();
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+
+    return body;
+}
+
+#[tokio::test]
+pub async fn should_send_signed_notification() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let callback_url = format!("http://{}/callback", listener.local_addr().unwrap());
+    let receiver = std::thread::spawn(move || receive_one(listener));
+
+    let expires_at = (Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+    let payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        &expires_at,
+        "foobar".to_owned(),
+        callback_url,
+        "".to_owned(),
+    );
+    let qr = client.create_qr(&payload, &token).await.unwrap();
+    let pay_id = pay(&server, &qr.qr_id, &token).await;
+
+    let body = receiver.join().unwrap();
+    let notification: NotificationPayload = serde_json::from_slice(&body).unwrap();
+    assert_eq!(notification, server.notifications()[0]);
+
+    let valid = notification
+        .validate_signature(server.config().signature_key)
+        .unwrap();
+    assert_eq!(valid.0.pay_id(), &pay_id);
+    assert_eq!(server.qr(&qr.qr_id).unwrap().status, QRStatus::Paid);
+}
+
+#[tokio::test]
+pub async fn should_flag_redelivered_notification() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let expires_at = (Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+    let payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        &expires_at,
+        "foobar".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+    );
+    let qr = client.create_qr(&payload, &token).await.unwrap();
+    pay(&server, &qr.qr_id, &token).await;
+
+    let webhook = Webhook::new(server.config().signature_key)
+        .with_store(InMemoryNotificationStore::new())
+        .with_max_age(Duration::from_secs(300));
+    let notification = server.notifications()[0].clone();
+
+    let first = webhook.receive(notification.clone()).unwrap();
+    let redelivered = webhook.receive(notification).unwrap();
+
+    assert!(!first.duplicate);
+    assert!(redelivered.duplicate);
+    assert_eq!(first.notification, redelivered.notification);
+}
+
+#[tokio::test]
+pub async fn should_send_signed_refund_callback() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let expires_at = (Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+    let payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        &expires_at,
+        "foobar".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+    );
+    let qr = client.create_qr(&payload, &token).await.unwrap();
+    let pay_id = pay(&server, &qr.qr_id, &token).await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let callback_url = format!("http://{}/refund", listener.local_addr().unwrap());
+    let receiver = std::thread::spawn(move || receive_one(listener));
+
+    let mut refund = RefundPayment::partial(Decimal::from(30), "foobar".to_owned());
+    refund.callback_url = Some(callback_url);
+    let response = client
+        .refund_payment(&pay_id, &refund, &token)
+        .await
+        .unwrap();
+
+    let body = receiver.join().unwrap();
+    let callback: RefundNotificationPayload = serde_json::from_slice(&body).unwrap();
+    let valid = callback
+        .validate_signature(server.config().signature_key)
+        .unwrap();
+    assert_eq!(valid.0.pay_id(), &pay_id);
+    assert_eq!(Some(valid.0.refund_id()), response.refund_id.as_deref());
+    assert_eq!(valid.0.amount(), Decimal::from(30));
+    assert_eq!(valid.0.status(), &PaymentStatus::PartiallyRefunded);
+}
@@ -0,0 +1,38 @@
+use std::time::{Duration, Instant};
+
+use maib_client::{
+    client::Client,
+    error::Error,
+    models::request::ListQRs,
+    rate_limit::{Rate, RateLimiter},
+    testing::FakeServer,
+};
+
+#[tokio::test]
+pub async fn should_retry_after_too_many_requests() {
+    let server = FakeServer::start();
+    let token = server.issue_token();
+    let limiter = RateLimiter::global(Rate::per_second(10));
+    let client = Client::new(server.base_url()).with_rate_limiter(limiter);
+
+    server.throttle(1, 1);
+    let started = Instant::now();
+    let page = client.list_qrs(&ListQRs::default(), &token).await.unwrap();
+
+    assert_eq!(page.total_count, 0);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+pub async fn should_fail_once_retries_are_exhausted() {
+    let server = FakeServer::start();
+    let token = server.issue_token();
+    let limiter = RateLimiter::new().with_max_retries(0);
+    let client = Client::new(server.base_url()).with_rate_limiter(limiter);
+
+    server.throttle(1, 0);
+    let result = client.list_qrs(&ListQRs::default(), &token).await;
+
+    assert!(matches!(result, Err(Error::Http(_))));
+    assert!(client.list_qrs(&ListQRs::default(), &token).await.is_ok());
+}
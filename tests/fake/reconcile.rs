@@ -0,0 +1,80 @@
+use chrono::{Duration, Utc};
+use maib_client::{
+    client::Client,
+    models::{
+        request::{CreateQR, RefundPayment},
+        AccessToken, Currency, QRId,
+    },
+    reconcile::{self, ExpectedPayment, ExpectedStatus},
+    testing::FakeServer,
+};
+use rust_decimal::Decimal;
+
+use crate::transitions::pay;
+
+async fn create_qr(client: &Client, token: &AccessToken, order_id: &str) -> QRId {
+    let expires_at = (Utc::now() + Duration::days(1)).to_rfc3339();
+    let mut payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        &expires_at,
+        "foobar".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+    );
+    payload.order_id = Some(order_id);
+
+    return client.create_qr(&payload, token).await.unwrap().qr_id;
+}
+
+fn expected(order_id: &str, status: ExpectedStatus) -> ExpectedPayment {
+    return ExpectedPayment {
+        order_id: order_id.to_owned(),
+        amount: Decimal::from(100),
+        currency: Currency::MDL,
+        status,
+    };
+}
+
+#[tokio::test]
+pub async fn should_reconcile_payments_in_date_range() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let paid = create_qr(&client, &token, "paid").await;
+    pay(&server, &paid, &token).await;
+    let refunded = create_qr(&client, &token, "refunded").await;
+    let refunded = pay(&server, &refunded, &token).await;
+    client
+        .refund_payment(&refunded, &RefundPayment::full("foobar".to_owned()), &token)
+        .await
+        .unwrap();
+    let unknown = create_qr(&client, &token, "unknown").await;
+    pay(&server, &unknown, &token).await;
+
+    let local = vec![
+        expected("paid", ExpectedStatus::Paid),
+        expected("refunded", ExpectedStatus::Paid),
+        expected("never-paid", ExpectedStatus::Paid),
+    ];
+    let from = Utc::now() - Duration::hours(1);
+    let to = Utc::now() + Duration::hours(1);
+
+    let report = reconcile::reconcile(&client, &token, local.clone(), from, to)
+        .await
+        .unwrap();
+
+    assert_eq!(report.matched.len(), 1);
+    assert_eq!(report.status_mismatches[0].expected.order_id, "refunded");
+    assert_eq!(report.missing_at_maib[0].order_id, "never-paid");
+    assert_eq!(
+        report.missing_locally[0].order_id.as_deref(),
+        Some("unknown")
+    );
+
+    let past = Utc::now() - Duration::days(2);
+    let report = reconcile::reconcile(&client, &token, local, past, from)
+        .await
+        .unwrap();
+    assert_eq!(report.missing_at_maib.len(), 3);
+}
@@ -0,0 +1,199 @@
+use chrono::{Duration, Utc};
+use maib_client::{
+    client::Client,
+    error::Error,
+    models::{
+        request::{CancelQR, CreateQR, RefundPayment},
+        AccessToken, PaymentId, PaymentStatus, QRId, QRStatus,
+    },
+    testing::FakeServer,
+    wait::WaitOptions,
+};
+use rust_decimal::Decimal;
+
+async fn create_qr(client: &Client, token: &AccessToken, callback_url: String) -> QRId {
+    let expires_at = (Utc::now() + Duration::days(1)).to_rfc3339();
+    let payload = CreateQR::new_dynamic_with_fixed_amount(
+        Decimal::from(100),
+        &expires_at,
+        "foobar".to_owned(),
+        callback_url,
+        "".to_owned(),
+    );
+
+    return client.create_qr(&payload, token).await.unwrap().qr_id;
+}
+
+pub async fn pay(server: &FakeServer, qr_id: &QRId, token: &AccessToken) -> PaymentId {
+    let res = reqwest::Client::new()
+        .post(format!("{}/v2/mia/test-pay", server.base_url()))
+        .bearer_auth(token.as_str())
+        .json(&serde_json::json!({
+            "qrId": qr_id,
+            "amount": 100,
+            "iban": "MD88AG000000011621810140",
+            "currency": "MDL",
+            "payerName": "John D."
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let payload: serde_json::Value = res.json().await.unwrap();
+    let pay_id = payload["result"]["payId"].as_str().unwrap();
+
+    return PaymentId::new(pay_id.to_owned());
+}
+
+#[tokio::test]
+pub async fn should_pay_and_refund_once() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let qr_id = create_qr(&client, &token, "".to_owned()).await;
+    let pay_id = pay(&server, &qr_id, &token).await;
+
+    let qr = client.get_qr(&qr_id, &token).await.unwrap();
+    assert_eq!(qr.status, QRStatus::Paid);
+
+    let reason = RefundPayment::full("foobar".to_owned());
+    let refund = client
+        .refund_payment(&pay_id, &reason, &token)
+        .await
+        .unwrap();
+    assert_eq!(refund.status, PaymentStatus::Refunded);
+
+    let payment = client.get_payment(&pay_id, &token).await.unwrap();
+    assert_eq!(payment.status, PaymentStatus::Refunded);
+    assert!(payment.refunded_at.is_some());
+
+    let second = client.refund_payment(&pay_id, &reason, &token).await;
+    assert!(matches!(second, Err(Error::Api(_))));
+}
+
+#[tokio::test]
+pub async fn should_not_cancel_paid_qr() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let qr_id = create_qr(&client, &token, "".to_owned()).await;
+    pay(&server, &qr_id, &token).await;
+
+    let reason = CancelQR {
+        reason: "foobar".to_owned(),
+    };
+    let result = client.cancel_qr(&qr_id, &reason, &token).await;
+
+    let Err(Error::Api(errors)) = result else {
+        panic!("expected api error, got {result:?}");
+    };
+    assert_eq!(errors[0].code(), "qrNotActive");
+}
+
+#[tokio::test]
+pub async fn should_wait_until_qr_is_paid() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+    let qr_id = create_qr(&client, &token, "".to_owned()).await;
+
+    let options = WaitOptions {
+        initial_interval: std::time::Duration::from_millis(10),
+        timeout: Some(std::time::Duration::from_secs(5)),
+        ..Default::default()
+    };
+    let wait = client.wait_for_qr_completion(&qr_id, &token, options);
+    let pay = async {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        pay(&server, &qr_id, &token).await;
+    };
+
+    let (qr, _) = tokio::join!(wait, pay);
+    assert_eq!(qr.unwrap().status, QRStatus::Paid);
+}
+
+#[tokio::test]
+pub async fn should_refund_partially_until_nothing_remains() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let qr_id = create_qr(&client, &token, "".to_owned()).await;
+    let pay_id = pay(&server, &qr_id, &token).await;
+
+    let partial = RefundPayment::partial(Decimal::from(40), "foobar".to_owned());
+    let refund = client
+        .refund_payment(&pay_id, &partial, &token)
+        .await
+        .unwrap();
+    assert_eq!(refund.status, PaymentStatus::PartiallyRefunded);
+    assert_eq!(refund.amount, Some(Decimal::from(40)));
+
+    let payment = client.get_payment(&pay_id, &token).await.unwrap();
+    assert_eq!(payment.remaining_amount(), Decimal::from(60));
+
+    let too_much = RefundPayment::partial(Decimal::from(61), "foobar".to_owned());
+    let result = client
+        .refund_payment_checked(&payment, &too_much, &token)
+        .await;
+    assert_eq!(
+        result,
+        Err(Error::RefundExceedsRemaining {
+            requested: Decimal::from(61),
+            remaining: Decimal::from(60),
+        })
+    );
+
+    let rest = RefundPayment::full("foobar".to_owned());
+    let refund = client
+        .refund_payment_checked(&payment, &rest, &token)
+        .await
+        .unwrap();
+    assert_eq!(refund.status, PaymentStatus::Refunded);
+    assert_eq!(refund.amount, Some(Decimal::from(60)));
+
+    let payment = client.get_payment(&pay_id, &token).await.unwrap();
+    assert_eq!(payment.remaining_amount(), Decimal::ZERO);
+    assert!(payment.check_refund(&rest).is_err());
+}
+
+#[tokio::test]
+pub async fn should_refund_idempotently() {
+    let server = FakeServer::start();
+    let client = Client::new(server.base_url());
+    let token = server.issue_token();
+
+    let qr_id = create_qr(&client, &token, "".to_owned()).await;
+    let pay_id = pay(&server, &qr_id, &token).await;
+    let partial = RefundPayment::partial(Decimal::from(30), "foobar".to_owned());
+
+    let first = client
+        .refund_payment_idempotent(&pay_id, &partial, Decimal::ZERO, &token)
+        .await
+        .unwrap();
+    let retry = client
+        .refund_payment_idempotent(&pay_id, &partial, Decimal::ZERO, &token)
+        .await
+        .unwrap();
+
+    assert_eq!(first.status, PaymentStatus::PartiallyRefunded);
+    assert_eq!(retry.amount, Some(Decimal::from(30)));
+    let payment = client.get_payment(&pay_id, &token).await.unwrap();
+    assert_eq!(payment.refunded_amount, Some(Decimal::from(30)));
+
+    let full = RefundPayment::full("foobar".to_owned());
+    for _ in 0..2 {
+        let refund = client
+            .refund_payment_idempotent(&pay_id, &full, Decimal::from(30), &token)
+            .await
+            .unwrap();
+        assert_eq!(refund.status, PaymentStatus::Refunded);
+    }
+
+    let stale = client
+        .refund_payment_idempotent(&pay_id, &partial, Decimal::ZERO, &token)
+        .await;
+    assert!(matches!(stale, Err(Error::RefundedAmountMismatch { .. })));
+}
@@ -1,25 +1,78 @@
 use chrono::{Duration, Utc};
 use maib_client::{
     client::Client,
+    config::{Config, ConfigError, ConfigLoader},
     error::Result,
     models::{
-        AccessToken, PaymentId, QRId,
         request::CreateQR,
         response::{self},
+        AccessToken, PaymentId, QRId,
     },
+    testing::FakeServer,
 };
 use rust_decimal::Decimal;
-use std::{env, str::FromStr};
+use std::{str::FromStr, sync::OnceLock};
+
+/// Fake server used when sandbox is not configured.
+fn fake_server() -> &'static FakeServer {
+    static SERVER: OnceLock<FakeServer> = OnceLock::new();
+    return SERVER.get_or_init(FakeServer::start);
+}
+
+/// Deprecated name of `MAIB_SANDBOX_BASE_URL`, still read if the new
+/// one is not set.
+const DEPRECATED_BASE_URL: &str = "MAIB_SANDBOX_BASE_PATH";
+
+fn env(name: &str) -> Option<String> {
+    let value = std::env::var(name).ok().filter(|value| !value.is_empty());
+    if name == "MAIB_SANDBOX_BASE_URL" {
+        return value.or_else(|| env(DEPRECATED_BASE_URL));
+    }
+
+    return value;
+}
+
+/// Sandbox configuration read from `MAIB_SANDBOX_*` env variables,
+/// [None] if sandbox is not configured at all.
+///
+/// Panics if sandbox is configured partially, so tests do not silently
+/// run against the fake server.
+fn sandbox_config() -> Option<Config> {
+    let config = ConfigLoader::new()
+        .env_prefix("MAIB_SANDBOX_")
+        .load_with_env(env);
+
+    return match config {
+        Ok(config) => Some(config),
+        Err(ConfigError::Missing("base_url")) => {
+            if env("MAIB_SANDBOX_ACCESS_TOKEN").is_some() {
+                panic!("MAIB_SANDBOX_ACCESS_TOKEN is set, but MAIB_SANDBOX_BASE_URL is not");
+            }
+            None
+        }
+        Err(err) => panic!("invalid sandbox configuration: {err}"),
+    };
+}
 
 pub fn base_url_path() -> String {
-    let base_url_path = env::var("MAIB_SANDBOX_BASE_PATH").unwrap();
-    return base_url_path;
+    return match sandbox_config() {
+        Some(config) => config.base_url,
+        None => fake_server().base_url(),
+    };
 }
 
 pub fn setup() -> (Client, AccessToken) {
-    let access_token = env::var("MAIB_SANDBOX_ACCESS_TOKEN").unwrap();
+    let Some(config) = sandbox_config() else {
+        let server = fake_server();
+        return (Client::new(server.base_url()), server.issue_token());
+    };
+
+    let access_token = config
+        .access_token
+        .clone()
+        .expect("MAIB_SANDBOX_ACCESS_TOKEN is not set");
 
-    return (Client::new(base_url_path()), AccessToken::new(access_token));
+    return (config.client().unwrap(), access_token);
 }
 
 pub async fn create_fix_payment_qr() -> (Client, AccessToken, response::CreateQRResponse) {
@@ -28,7 +28,7 @@ pub async fn should_refund_payment() {
     let pay_id = pay_id.unwrap();
 
     let detail = client
-        .refund_payment(&pay_id, &RefundPayment { reason: "foobar".to_owned() }, &token)
+        .refund_payment(&pay_id, &RefundPayment::full("foobar".to_owned()), &token)
         .await;
 
     eprintln!("{detail:?}");